@@ -0,0 +1,74 @@
+use std::io::{Read, Seek};
+
+use zip::{result::ZipError, ZipArchive};
+
+use crate::LutError;
+
+/// Reads the uncompressed bytes for the file `name` in the zip archive from `reader`.
+/// The `name` is the full path of the file in the archive like `mod/camera/color_grading_lut.nutexb`.
+pub fn read_zip_entry<R: Read + Seek>(reader: R, name: &str) -> Result<Vec<u8>, LutError> {
+    let mut archive = ZipArchive::new(reader).map_err(zip_error)?;
+    let mut file = archive.by_name(name).map_err(zip_error)?;
+
+    // The size in the archive header isn't trusted, so let the buffer grow as data is read.
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn zip_error(e: ZipError) -> LutError {
+    match e {
+        ZipError::Io(e) => LutError::Io(e),
+        e => LutError::Parse(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+    use crate::{format, Lut3dLinear};
+
+    fn zip_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, bytes) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(bytes).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn read_nutexb_entry() {
+        let lut = Lut3dLinear::default_stage();
+        let nutexb = format::from_extension("nutexb").unwrap();
+        let mut bytes = Vec::new();
+        nutexb.write(&lut, &mut bytes).unwrap();
+
+        let zip = zip_bytes(&[
+            ("readme.txt", b"sepia"),
+            ("mod/color_grading_lut.nutexb", &bytes),
+        ]);
+        let entry = read_zip_entry(Cursor::new(zip), "mod/color_grading_lut.nutexb").unwrap();
+        assert_eq!(bytes, entry);
+
+        let entry_lut = nutexb.read(&mut Cursor::new(entry)).unwrap();
+        assert_eq!(lut.to_rgba(), entry_lut.to_rgba());
+    }
+
+    #[test]
+    fn read_missing_entry() {
+        let zip = zip_bytes(&[("readme.txt", b"sepia")]);
+        let result = read_zip_entry(Cursor::new(zip), "color_grading_lut.nutexb");
+        assert!(matches!(result, Err(LutError::Parse(_))));
+    }
+
+    #[test]
+    fn read_invalid_archive() {
+        let result = read_zip_entry(Cursor::new(b"not a zip".to_vec()), "lut.cube");
+        assert!(matches!(result, Err(LutError::Parse(_))));
+    }
+}
@@ -0,0 +1,71 @@
+use ndarray::Array4;
+
+use crate::{Lut3dLinear, LutError};
+
+impl Lut3dLinear {
+    /// Converts the LUT to an array with shape `[size, size, size, 4]`.
+    /// The axes are the blue, green, and red input followed by the RGBA channel,
+    /// so `array[[z, y, x, c]]` is channel `c` of the node at red `x`, green `y`, and blue `z`.
+    pub fn to_ndarray(&self) -> Array4<f32> {
+        // The data is already in row-major ZYX order, so this never fails.
+        Array4::from_shape_vec((self.size, self.size, self.size, 4), self.data.clone()).unwrap()
+    }
+
+    /// Creates a LUT from an array with the same shape and axis order as [Lut3dLinear::to_ndarray].
+    pub fn from_ndarray(array: &Array4<f32>) -> Result<Self, LutError> {
+        let size = array.shape()[0];
+        if array.shape() != [size, size, size, 4] {
+            return Err(LutError::InvalidDimensions(
+                "Invalid shape. Expected an array with shape [size, size, size, 4].",
+            ));
+        }
+
+        Ok(Self {
+            size,
+            data: array.iter().copied().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::ShapeBuilder;
+
+    use super::*;
+
+    #[test]
+    fn ndarray_round_trip() {
+        let lut = Lut3dLinear::default_stage().rotate_hue(30.0);
+        assert_eq!(lut, Lut3dLinear::from_ndarray(&lut.to_ndarray()).unwrap());
+    }
+
+    #[test]
+    fn to_ndarray_axis_order() {
+        let lut = Lut3dLinear::from_fn(4, |rgb| rgb);
+        let array = lut.to_ndarray();
+        assert_eq!(&[4, 4, 4, 4], array.shape());
+
+        // Red changes along the last spatial axis.
+        assert_eq!(1.0, array[[0, 0, 3, 0]]);
+        assert_eq!(0.0, array[[0, 0, 3, 2]]);
+        assert_eq!(1.0, array[[3, 0, 0, 2]]);
+        assert_eq!(1.0, array[[3, 0, 0, 3]]);
+    }
+
+    #[test]
+    fn from_ndarray_column_major() {
+        // The memory layout is different, but the logical order is the same.
+        let lut = Lut3dLinear::from_fn(4, |rgb| rgb);
+        let mut array = Array4::zeros((4, 4, 4, 4).f());
+        array.assign(&lut.to_ndarray());
+        assert_eq!(lut, Lut3dLinear::from_ndarray(&array).unwrap());
+    }
+
+    #[test]
+    fn from_ndarray_invalid_shape() {
+        for shape in [(4, 4, 2, 4), (4, 4, 4, 3), (0, 1, 1, 4)] {
+            let result = Lut3dLinear::from_ndarray(&Array4::zeros(shape));
+            assert!(matches!(result, Err(LutError::InvalidDimensions(_))));
+        }
+    }
+}
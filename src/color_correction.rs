@@ -1,29 +1,216 @@
-use crate::Lut3dLinear;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use serde::{Deserialize, Serialize};
+
+use crate::{node_coord, normalized_coord, Cancelled, Lut3dLinear, LutError};
+
+/// Constants for the post processing applied in game after the color grading LUT.
+/// The defaults match the values used by [correct_lut].
+/// See the [color_correction](https://github.com/ScanMountGoat/Smush-LUT/blob/master/color_correction.md)
+/// page for how these values are used.
+///
+/// Parameters can be saved to TOML or JSON files to share measured values for a stage.
+/// Missing fields in a file use the default values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StageParams {
+    /// The scale applied to the input color when calculating LUT texture coordinates.
+    pub texcoord_scale: f32,
+    /// The offset added to the scaled input color when calculating LUT texture coordinates.
+    pub texcoord_offset: f32,
+    /// The amount to preserve the difference between the LUT value and the input color.
+    pub compression: f32,
+    /// The scale applied after compression.
+    pub gain: f32,
+    /// The exponent applied after the gain.
+    pub gamma: f32,
+    /// Overrides [StageParams::compression] with separate RGB values for stages that compress each channel differently.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_compression: Option<[f32; 3]>,
+    /// Overrides [StageParams::gain] with separate RGB values for stages that scale each channel differently.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_gain: Option<[f32; 3]>,
+    /// Calculate input colors from texel centers like GPU texture sampling
+    /// instead of treating each node as a texture coordinate.
+    pub half_texel_offset: bool,
+    /// Refine the closed form inverse of the post processing with a few Newton steps.
+    /// This reduces floating point error at the cost of slightly slower correction.
+    pub accurate: bool,
+}
+
+impl Default for StageParams {
+    fn default() -> Self {
+        Self {
+            texcoord_scale: 0.9375,
+            texcoord_offset: 0.03125,
+            compression: 0.99961,
+            gain: 1.3703,
+            gamma: 2.2,
+            channel_compression: None,
+            channel_gain: None,
+            half_texel_offset: false,
+            accurate: false,
+        }
+    }
+}
+
+impl StageParams {
+    /// Reads parameters from a TOML file or from a JSON file if the extension is `.json`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, LutError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        if is_json(path) {
+            serde_json::from_str(&text).map_err(|e| LutError::Parse(e.to_string()))
+        } else {
+            toml::from_str(&text).map_err(|e| LutError::Parse(e.to_string()))
+        }
+    }
+
+    /// Writes parameters to a TOML file or to a JSON file if the extension is `.json`.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), LutError> {
+        let path = path.as_ref();
+        // Parse errors are only for reading, so report serialization failures as IO errors.
+        let text = if is_json(path) {
+            serde_json::to_string_pretty(self).map_err(std::io::Error::from)?
+        } else {
+            toml::to_string(self)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+        };
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    fn compression(&self, c: usize) -> f32 {
+        self.channel_compression
+            .map(|values| values[c])
+            .unwrap_or(self.compression)
+    }
+
+    fn gain(&self, c: usize) -> f32 {
+        self.channel_gain
+            .map(|values| values[c])
+            .unwrap_or(self.gain)
+    }
+
+    // The post processing for channel c with c = 0, 1, 2 for R, G, B.
+    fn g_x(&self, xi: f32, x: f32, c: usize) -> f32 {
+        (((xi - x) * self.compression(c) + x) * self.gain(c))
+            .max(0.0)
+            .powf(self.gamma)
+    }
+
+    // g is only invertible if we fix x to create a function g_x.
+    // We're cheating slightly here by making x a parameter.
+    // Creating a shared function just makes the code cleaner.
+    fn g_x_inv(&self, xi: f32, x: f32, c: usize) -> f32 {
+        (((xi.max(0.0).powf(1.0 / self.gamma) / self.gain(c)) - x) / self.compression(c)) + x
+    }
+
+    // Solve g_x(xi, x) = target for xi starting from the closed form inverse.
+    fn solve_g_x(&self, target: f32, x: f32, c: usize) -> f32 {
+        let mut xi = self.g_x_inv(target, x, c);
+        if self.accurate {
+            for _ in 0..NEWTON_STEPS {
+                let (compression, gain) = (self.compression(c), self.gain(c));
+                let base = ((xi - x) * compression + x) * gain;
+                if base <= 0.0 {
+                    // g_x is clamped to 0.0 here, so there is no unique solution to refine.
+                    break;
+                }
+
+                let derivative = self.gamma * base.powf(self.gamma - 1.0) * compression * gain;
+                xi -= (self.g_x(xi, x, c) - target.max(0.0)) / derivative;
+            }
+        }
+        xi
+    }
+
+    fn f_inv(&self, fx: f32) -> f32 {
+        (fx - self.texcoord_offset) / self.texcoord_scale
+    }
+
+    // The texture coordinate for the node at index in a LUT with the given size.
+    fn texcoord(&self, index: usize, size: usize) -> f32 {
+        if self.half_texel_offset {
+            (index as f32 + 0.5) / size as f32
+        } else {
+            normalized_coord(index, size)
+        }
+    }
+}
+
+const NEWTON_STEPS: usize = 3;
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.eq_ignore_ascii_case("json"))
+        .unwrap_or(false)
+}
+
+/// Calculates the final stage LUT that produces the colors of `lut_edit` in game after post processing with `lut_stage`.
+///
+/// The LUTs don't need to be the same size.
+/// `lut_stage` is sampled with trilinear interpolation,
+/// and the result always has the same size as `lut_edit`.
 pub fn correct_lut(lut_edit: &Lut3dLinear, lut_stage: &Lut3dLinear) -> Lut3dLinear {
+    correct_lut_with_params(lut_edit, lut_stage, &StageParams::default())
+}
+
+/// Calculates the final stage LUT like [correct_lut] using custom post processing `params`.
+pub fn correct_lut_with_params(
+    lut_edit: &Lut3dLinear,
+    lut_stage: &Lut3dLinear,
+    params: &StageParams,
+) -> Lut3dLinear {
+    // The operation can't be cancelled, so this never fails.
+    correct_lut_cancellable(lut_edit, lut_stage, params, &AtomicBool::new(false)).unwrap()
+}
+
+/// Calculates the final stage LUT like [correct_lut_with_params].
+/// Returns [Cancelled] shortly after `cancel` is set to `true` from another thread.
+pub fn correct_lut_cancellable(
+    lut_edit: &Lut3dLinear,
+    lut_stage: &Lut3dLinear,
+    params: &StageParams,
+    cancel: &AtomicBool,
+) -> Result<Lut3dLinear, Cancelled> {
+    correct_lut_until(lut_edit, lut_stage, params, || {
+        cancel.load(Ordering::Relaxed)
+    })
+}
+
+// Checks for cancellation once for each z slice.
+fn correct_lut_until<F: FnMut() -> bool>(
+    lut_edit: &Lut3dLinear,
+    lut_stage: &Lut3dLinear,
+    params: &StageParams,
+    mut is_cancelled: F,
+) -> Result<Lut3dLinear, Cancelled> {
     // Calculate the final stage LUT for a LUT applied to a stage screenshot.
     let mut lut_final = Lut3dLinear::empty_rgba(lut_edit.size);
 
     // TODO: Figure out ways to make this more efficient.
     for z_index in 0..lut_edit.size {
+        if is_cancelled() {
+            return Err(Cancelled);
+        }
+
         for y_index in 0..lut_edit.size {
             for x_index in 0..lut_edit.size {
                 // TODO: Make functions over [f32; 4] so this can match the docs.
                 // Sample each point xi = f(x) in the lut.
                 // TODO: Test on empty lut?
-                let xi = [
-                    x_index as f32 / (lut_edit.size - 1) as f32,
-                    y_index as f32 / (lut_edit.size - 1) as f32,
-                    z_index as f32 / (lut_edit.size - 1) as f32,
-                ];
+                let xi = node_coord(x_index, y_index, z_index, lut_edit.size);
 
                 // result = lut_stage(xi)
                 let mut result = lut_stage.sample_rgba_trilinear(xi[0], xi[1], xi[2]);
 
                 // result = srgb(g_x(lut_stage(xi)))
-                let x = xi.map(f_inv);
+                let x = [x_index, y_index, z_index]
+                    .map(|i| params.f_inv(params.texcoord(i, lut_edit.size)));
                 for c in 0..3 {
-                    result[c] = srgb(g_x(result[c], x[c]));
+                    result[c] = srgb(params.g_x(result[c], x[c], c));
                 }
 
                 // result = lut_edit(srgb(g_x(lut_stage(xi))))
@@ -31,7 +218,7 @@ pub fn correct_lut(lut_edit: &Lut3dLinear, lut_stage: &Lut3dLinear) -> Lut3dLine
 
                 // result = g_x_inv(linear(lut_edit(srgb(g_x(lut_stage(xi))))))
                 for c in 0..3 {
-                    result[c] = g_x_inv(linear(result[c]), x[c]);
+                    result[c] = params.solve_g_x(linear(result[c]), x[c], c);
                 }
 
                 // Alpha is always 1.0.
@@ -44,25 +231,119 @@ pub fn correct_lut(lut_edit: &Lut3dLinear, lut_stage: &Lut3dLinear) -> Lut3dLine
         }
     }
 
-    lut_final
+    Ok(lut_final)
 }
 
-fn g_x(xi: f32, x: f32) -> f32 {
-    (((xi - x) * 0.99961 + x) * 1.3703).max(0.0).powf(2.2)
+/// Recovers the edit LUT from a final stage LUT created with [correct_lut].
+/// This is the inverse of [correct_lut] and can be used to extract an editable look from a finished LUT.
+/// The result has the same size as `lut_final`.
+pub fn uncorrect_lut(lut_final: &Lut3dLinear, lut_stage: &Lut3dLinear) -> Lut3dLinear {
+    uncorrect_lut_with_params(lut_final, lut_stage, &StageParams::default())
 }
 
-// g is only invertible if we fix x to create a function g_x.
-// We're cheating slightly here by making x a parameter.
-// Creating a shared function just makes the code cleaner.
-fn g_x_inv(xi: f32, x: f32) -> f32 {
-    (((xi.max(0.0).powf(1.0 / 2.2) / 1.3703) - x) / 0.99961) + x
+/// Recovers the edit LUT like [uncorrect_lut] using custom post processing `params`.
+pub fn uncorrect_lut_with_params(
+    lut_final: &Lut3dLinear,
+    lut_stage: &Lut3dLinear,
+    params: &StageParams,
+) -> Lut3dLinear {
+    // Solving lut_final(xi) = g_x_inv(linear(lut_edit(srgb(g_x(lut_stage(xi)))))) for lut_edit
+    // gives lut_edit(srgb(g_x(lut_stage(xi)))) = srgb(g_x(lut_final(xi))).
+    // Both sides are known for each node xi, so invert the argument to find lut_edit.
+    let size = lut_final.size;
+    let mut lut_input = Lut3dLinear::empty_rgba(size);
+    let mut lut_output = Lut3dLinear::empty_rgba(size);
+    for z_index in 0..size {
+        for y_index in 0..size {
+            for x_index in 0..size {
+                let xi = node_coord(x_index, y_index, z_index, size);
+                let x = [x_index, y_index, z_index].map(|i| params.f_inv(params.texcoord(i, size)));
+
+                let mut input = lut_stage.sample_rgba_trilinear(xi[0], xi[1], xi[2]);
+                let mut output = lut_final.sample_rgba_trilinear(xi[0], xi[1], xi[2]);
+                for c in 0..3 {
+                    input[c] = srgb(params.g_x(input[c], x[c], c));
+                    output[c] = srgb(params.g_x(output[c], x[c], c));
+                }
+                input[3] = 1.0;
+                output[3] = 1.0;
+
+                lut_input.set_rgba(x_index, y_index, z_index, input);
+                lut_output.set_rgba(x_index, y_index, z_index, output);
+            }
+        }
+    }
+
+    Lut3dLinear::correction_delta(&lut_input, &lut_output)
 }
 
-fn f_inv(fx: f32) -> f32 {
-    (fx - 0.03125) / 0.9375
+/// Applies `lut_stage` and then `lut_edit` to show the combined effect of an edit on a stage.
+/// This is the forward direction of the pipeline without any of the inverse steps in [correct_lut].
+/// The result has the same size as `lut_stage`.
+pub fn apply_stage(lut_edit: &Lut3dLinear, lut_stage: &Lut3dLinear) -> Lut3dLinear {
+    lut_stage.compose(lut_edit)
 }
 
-fn srgb(linear: f32) -> f32 {
+/// Generates GLSL functions for the post processing with `params` to apply the correction on the GPU.
+///
+/// `stage_to_edit_coord` converts a `lut_stage` color to the coordinates for sampling `lut_edit`,
+/// and `edit_to_final` converts the sampled `lut_edit` color to the final stage LUT value.
+/// Both take the `texcoord` of the node or pixel being corrected.
+/// This uses the closed form inverse, so [StageParams::accurate] is ignored.
+pub fn correction_glsl(params: &StageParams) -> String {
+    let vec3 =
+        |values: [f32; 3]| format!("vec3({:?}, {:?}, {:?})", values[0], values[1], values[2]);
+    let compression = vec3([0, 1, 2].map(|c| params.compression(c)));
+    let gain = vec3([0, 1, 2].map(|c| params.gain(c)));
+
+    format!(
+        r#"// Generated by smush_lut.
+// color_final = edit_to_final(texture(lut_edit, stage_to_edit_coord(texture(lut_stage, texcoord).rgb, texcoord)).rgb, texcoord)
+const float TEXCOORD_SCALE = {texcoord_scale:?};
+const float TEXCOORD_OFFSET = {texcoord_offset:?};
+const vec3 COMPRESSION = {compression};
+const vec3 GAIN = {gain};
+const float GAMMA = {gamma:?};
+
+vec3 linear_to_srgb(vec3 color) {{
+    vec3 lo = 12.92 * color;
+    vec3 hi = 1.055 * pow(max(color, vec3(0.0)), vec3(1.0 / 2.4)) - 0.055;
+    return mix(hi, lo, vec3(lessThanEqual(color, vec3(0.0031308))));
+}}
+
+vec3 srgb_to_linear(vec3 color) {{
+    vec3 lo = color / 12.92;
+    vec3 hi = pow(max((color + 0.055) / 1.055, vec3(0.0)), vec3(2.4));
+    return mix(hi, lo, vec3(lessThanEqual(color, vec3(0.04045))));
+}}
+
+vec3 f_inv(vec3 fx) {{
+    return (fx - TEXCOORD_OFFSET) / TEXCOORD_SCALE;
+}}
+
+vec3 g_x(vec3 xi, vec3 x) {{
+    return pow(max(((xi - x) * COMPRESSION + x) * GAIN, vec3(0.0)), vec3(GAMMA));
+}}
+
+vec3 g_x_inv(vec3 xi, vec3 x) {{
+    return (pow(max(xi, vec3(0.0)), vec3(1.0 / GAMMA)) / GAIN - x) / COMPRESSION + x;
+}}
+
+vec3 stage_to_edit_coord(vec3 stage_color, vec3 texcoord) {{
+    return linear_to_srgb(g_x(stage_color, f_inv(texcoord)));
+}}
+
+vec3 edit_to_final(vec3 edit_color, vec3 texcoord) {{
+    return g_x_inv(srgb_to_linear(edit_color), f_inv(texcoord));
+}}
+"#,
+        texcoord_scale = params.texcoord_scale,
+        texcoord_offset = params.texcoord_offset,
+        gamma = params.gamma,
+    )
+}
+
+pub(crate) fn srgb(linear: f32) -> f32 {
     if linear <= 0.0031308 {
         12.92 * linear
     } else {
@@ -70,7 +351,7 @@ fn srgb(linear: f32) -> f32 {
     }
 }
 
-fn linear(srgb: f32) -> f32 {
+pub(crate) fn linear(srgb: f32) -> f32 {
     if srgb <= 0.04045 {
         srgb / 12.92
     } else {
@@ -83,6 +364,7 @@ mod tests {
     use approx::assert_relative_eq;
 
     use super::*;
+    use crate::{create_identity_lut_f32, index3d};
 
     fn f(srgb: f32) -> f32 {
         srgb * 0.9375 + 0.03125
@@ -103,8 +385,9 @@ mod tests {
         // Check that these functions are inverses of each other.
         for x in 0..255 {
             let x = x as f32 / 255.0;
-            assert_relative_eq!(x, f(f_inv(x)), epsilon = 0.0001f32);
-            assert_relative_eq!(x, f_inv(f(x)), epsilon = 0.0001f32);
+            let params = StageParams::default();
+            assert_relative_eq!(x, f(params.f_inv(x)), epsilon = 0.0001f32);
+            assert_relative_eq!(x, params.f_inv(f(x)), epsilon = 0.0001f32);
         }
     }
 
@@ -112,13 +395,61 @@ mod tests {
     fn g_g_x_inv() {
         // Check that these functions are inverses of each other.
         for x in 0..255 {
+            let params = StageParams::default();
             let fx = x as f32 / 255.0;
-            let x = f_inv(fx);
-            assert_relative_eq!(fx, g_x(g_x_inv(fx, x), x), epsilon = 0.0001f32);
-            assert_relative_eq!(fx, g_x_inv(g_x(fx, x), x), epsilon = 0.0001f32);
+            let x = params.f_inv(fx);
+            assert_relative_eq!(
+                fx,
+                params.g_x(params.g_x_inv(fx, x, 0), x, 0),
+                epsilon = 0.0001f32
+            );
+            assert_relative_eq!(
+                fx,
+                params.g_x_inv(params.g_x(fx, x, 0), x, 0),
+                epsilon = 0.0001f32
+            );
         }
     }
 
+    #[test]
+    fn stage_params_from_toml_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("params.toml");
+        std::fs::write(&path, "gain = 1.5\ngamma = 2.4\n").unwrap();
+
+        let params = StageParams::from_file(&path).unwrap();
+        let expected = StageParams {
+            gain: 1.5,
+            gamma: 2.4,
+            ..Default::default()
+        };
+        assert_eq!(expected, params);
+
+        // The loaded values should affect the correction.
+        let lut_edit = Lut3dLinear::identity();
+        let lut_stage = Lut3dLinear::default_stage();
+        let corrected = correct_lut_with_params(&lut_edit, &lut_stage, &params);
+        assert_eq!(
+            correct_lut_with_params(&lut_edit, &lut_stage, &expected),
+            corrected
+        );
+        assert_ne!(correct_lut(&lut_edit, &lut_stage), corrected);
+    }
+
+    #[test]
+    fn stage_params_json_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("params.json");
+
+        let params = StageParams {
+            compression: 0.5,
+            half_texel_offset: true,
+            ..Default::default()
+        };
+        params.write_to_file(&path).unwrap();
+        assert_eq!(params, StageParams::from_file(&path).unwrap());
+    }
+
     #[test]
     fn correct_identity_lut() {
         let lut_edit = Lut3dLinear::identity();
@@ -129,6 +460,93 @@ mod tests {
         assert_relative_eq!(corrected.data[..], lut_edit.data[..], epsilon = 0.1f32);
     }
 
+    // The largest relative error in the post processing of the corrected values
+    // compared to the linear values the correction solves for with identity LUTs.
+    fn max_post_processing_residual(corrected: &Lut3dLinear, params: &StageParams) -> f32 {
+        let identity = Lut3dLinear::identity();
+        let size = corrected.size;
+        let mut residual = 0.0f32;
+        for z in 0..size {
+            for y in 0..size {
+                for x in 0..size {
+                    let xi = node_coord(x, y, z, size);
+                    let coords = [x, y, z].map(|i| params.f_inv(params.texcoord(i, size)));
+
+                    let mut edit = identity.sample_rgba_trilinear(xi[0], xi[1], xi[2]);
+                    for c in 0..3 {
+                        edit[c] = srgb(params.g_x(edit[c], coords[c], c));
+                    }
+                    let edit = identity.sample_rgba_trilinear(edit[0], edit[1], edit[2]);
+
+                    let i = index3d(x, y, z, size, size) * 4;
+                    for c in 0..3 {
+                        let target = linear(edit[c]);
+                        if target > 0.0 {
+                            let value = params.g_x(corrected.data[i + c], coords[c], c);
+                            residual = residual.max((value - target).abs() / target);
+                        }
+                    }
+                }
+            }
+        }
+        residual
+    }
+
+    #[test]
+    fn correct_identity_lut_accurate() {
+        // A large gamma amplifies the rounding error of the closed form inverse.
+        let params = StageParams {
+            gamma: 6.0,
+            ..Default::default()
+        };
+        let accurate_params = StageParams {
+            accurate: true,
+            ..params.clone()
+        };
+
+        let lut = Lut3dLinear::identity();
+        let closed_form = correct_lut_with_params(&lut, &lut, &params);
+        let accurate = correct_lut_with_params(&lut, &lut, &accurate_params);
+
+        let error = max_post_processing_residual(&closed_form, &params);
+        let error_accurate = max_post_processing_residual(&accurate, &params);
+        // The remaining error is from rounding the targets themselves.
+        assert!(error > 1e-6, "{}", error);
+        assert!(error_accurate < error, "{} {}", error, error_accurate);
+        assert!(error_accurate < 1e-6, "{}", error_accurate);
+    }
+
+    #[test]
+    fn solve_g_x_accurate() {
+        // A large gamma amplifies the rounding error of the closed form inverse.
+        let params = StageParams {
+            gamma: 6.0,
+            ..Default::default()
+        };
+        let accurate_params = StageParams {
+            accurate: true,
+            ..params.clone()
+        };
+
+        let mut closed_form = 0.0f32;
+        let mut refined = 0.0f32;
+        for x in 0..256 {
+            let fx = x as f32 / 255.0;
+            let x = params.f_inv(fx);
+            let target = params.g_x(fx, x, 0);
+            if target <= 0.0 {
+                continue;
+            }
+
+            // The exact solution fx is representable, so only the error of the solver remains.
+            let relative_error = |xi: f32| (params.g_x(xi, x, 0) - target).abs() / target;
+            closed_form = closed_form.max(relative_error(params.solve_g_x(target, x, 0)));
+            refined = refined.max(relative_error(accurate_params.solve_g_x(target, x, 0)));
+        }
+        assert!(closed_form > 1e-6, "{}", closed_form);
+        assert!(refined < 1e-7, "{}", refined);
+    }
+
     #[test]
     fn correct_identity_stage_lut() {
         let lut_edit = Lut3dLinear::identity();
@@ -138,4 +556,206 @@ mod tests {
         let corrected = correct_lut(&lut_edit, &lut_stage);
         assert_relative_eq!(corrected.data[..], lut_stage.data[..], epsilon = 0.1f32);
     }
+
+    #[test]
+    fn correct_identity_stage_lut_different_sizes() {
+        let lut_edit = Lut3dLinear {
+            size: 8,
+            data: create_identity_lut_f32(8),
+        };
+        let lut_stage = Lut3dLinear::default_stage();
+
+        // The output size should match the edit LUT rather than the stage LUT.
+        let corrected = correct_lut(&lut_edit, &lut_stage);
+        assert_eq!(8, corrected.size);
+        assert_eq!(8 * 8 * 8 * 4, corrected.data.len());
+        assert_relative_eq!(
+            corrected.data[..],
+            lut_stage.resample(8).data[..],
+            epsilon = 0.1f32
+        );
+    }
+
+    #[test]
+    fn apply_stage_identity_edit() {
+        let lut_edit = Lut3dLinear::identity();
+        let lut_stage = Lut3dLinear::default_stage();
+
+        let result = apply_stage(&lut_edit, &lut_stage);
+        assert_relative_eq!(result.data[..], lut_stage.data[..], epsilon = 0.0001f32);
+    }
+
+    #[test]
+    fn apply_stage_identity_stage() {
+        let lut_edit = Lut3dLinear::default_stage();
+        let lut_stage = Lut3dLinear::identity();
+
+        let result = apply_stage(&lut_edit, &lut_stage);
+        assert_relative_eq!(result.data[..], lut_edit.data[..], epsilon = 0.0001f32);
+    }
+
+    #[test]
+    fn correct_lut_cancelled() {
+        let lut_edit = Lut3dLinear::identity();
+        let lut_stage = Lut3dLinear::default_stage();
+
+        let cancel = AtomicBool::new(true);
+        let result =
+            correct_lut_cancellable(&lut_edit, &lut_stage, &StageParams::default(), &cancel);
+        assert_eq!(Err(Cancelled), result);
+    }
+
+    #[test]
+    fn correct_lut_cancelled_mid_run() {
+        let lut_edit = Lut3dLinear::identity();
+        let lut_stage = Lut3dLinear::default_stage();
+
+        let cancel = AtomicBool::new(false);
+        let mut slices = 0;
+        let result = correct_lut_until(&lut_edit, &lut_stage, &StageParams::default(), || {
+            // Cancel after some of the slices have already been corrected.
+            if slices == 4 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            slices += 1;
+            cancel.load(Ordering::Relaxed)
+        });
+        assert_eq!(Err(Cancelled), result);
+        assert_eq!(5, slices);
+    }
+
+    #[test]
+    fn correct_lut_not_cancelled() {
+        let lut_edit = Lut3dLinear::identity();
+        let lut_stage = Lut3dLinear::default_stage();
+
+        let cancel = AtomicBool::new(false);
+        let result =
+            correct_lut_cancellable(&lut_edit, &lut_stage, &StageParams::default(), &cancel);
+        assert_eq!(Ok(correct_lut(&lut_edit, &lut_stage)), result);
+    }
+
+    fn max_error(a: &Lut3dLinear, b: &Lut3dLinear) -> f32 {
+        crate::difference(a, b).unwrap().max
+    }
+
+    #[test]
+    fn correct_identity_lut_half_texel_offset() {
+        let lut_edit = Lut3dLinear::identity();
+        let lut_stage = Lut3dLinear::identity();
+
+        let params = StageParams {
+            half_texel_offset: true,
+            ..Default::default()
+        };
+        let corrected = correct_lut_with_params(&lut_edit, &lut_stage, &params);
+        let error_offset = max_error(&corrected, &lut_edit);
+        let error = max_error(&correct_lut(&lut_edit, &lut_stage), &lut_edit);
+        assert!(error_offset < error);
+        assert!(error_offset < 0.00001);
+    }
+
+    #[test]
+    fn uncorrect_corrected_lut() {
+        let lut_edit = Lut3dLinear::from_matrix(
+            16,
+            [[0.8, 0.1, 0.05], [0.05, 0.85, 0.05], [0.05, 0.05, 0.9]],
+            [0.02; 3],
+        );
+        let lut_stage = Lut3dLinear::default_stage();
+
+        let corrected = correct_lut(&lut_edit, &lut_stage);
+        let uncorrected = uncorrect_lut(&corrected, &lut_stage);
+        assert!(max_error(&uncorrected, &lut_edit) < 0.0001);
+    }
+
+    #[test]
+    fn correct_uniform_channel_params() {
+        let lut_edit = Lut3dLinear::default_stage().rotate_hue(30.0);
+        let lut_stage = Lut3dLinear::default_stage();
+
+        let defaults = StageParams::default();
+        let params = StageParams {
+            channel_compression: Some([defaults.compression; 3]),
+            channel_gain: Some([defaults.gain; 3]),
+            ..Default::default()
+        };
+        assert_eq!(
+            correct_lut(&lut_edit, &lut_stage),
+            correct_lut_with_params(&lut_edit, &lut_stage, &params)
+        );
+    }
+
+    #[test]
+    fn correct_per_channel_params() {
+        let lut_edit = Lut3dLinear::identity();
+        let lut_stage = Lut3dLinear::identity();
+
+        // Only changing the red gain should only change the red channel.
+        let params = StageParams {
+            channel_gain: Some([1.5, 1.3703, 1.3703]),
+            ..Default::default()
+        };
+        let corrected = correct_lut_with_params(&lut_edit, &lut_stage, &params);
+        let expected = correct_lut(&lut_edit, &lut_stage);
+        for (rgba, expected) in corrected
+            .data
+            .chunks_exact(4)
+            .zip(expected.data.chunks_exact(4))
+        {
+            assert_relative_eq!(rgba[1..], expected[1..], epsilon = 0.00001f32);
+        }
+        assert_ne!(corrected, expected);
+    }
+
+    #[test]
+    fn stage_params_channel_toml_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("params.toml");
+        std::fs::write(&path, "channel_compression = [0.9, 0.95, 1.0]\n").unwrap();
+
+        let params = StageParams::from_file(&path).unwrap();
+        assert_eq!(Some([0.9, 0.95, 1.0]), params.channel_compression);
+        assert_eq!(None, params.channel_gain);
+
+        params.write_to_file(&path).unwrap();
+        assert_eq!(params, StageParams::from_file(&path).unwrap());
+    }
+
+    #[test]
+    fn stage_params_write_missing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["params.toml", "params.json"] {
+            let path = dir.path().join("missing").join(name);
+            assert!(matches!(
+                StageParams::default().write_to_file(&path),
+                Err(LutError::Io(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn correction_glsl_default_params() {
+        let glsl = correction_glsl(&StageParams::default());
+        assert!(glsl.contains("const float TEXCOORD_SCALE = 0.9375;"));
+        assert!(glsl.contains("const float TEXCOORD_OFFSET = 0.03125;"));
+        assert!(glsl.contains("const vec3 COMPRESSION = vec3(0.99961, 0.99961, 0.99961);"));
+        assert!(glsl.contains("const vec3 GAIN = vec3(1.3703, 1.3703, 1.3703);"));
+        assert!(glsl.contains("const float GAMMA = 2.2;"));
+        assert!(glsl.contains("vec3 stage_to_edit_coord(vec3 stage_color, vec3 texcoord) {"));
+        assert!(glsl.contains("vec3 edit_to_final(vec3 edit_color, vec3 texcoord) {"));
+        assert_eq!(glsl.matches('{').count(), glsl.matches('}').count());
+    }
+
+    #[test]
+    fn correction_glsl_channel_params() {
+        let params = StageParams {
+            gamma: 2.0,
+            channel_gain: Some([1.0, 1.5, 2.0]),
+            ..Default::default()
+        };
+        let glsl = correction_glsl(&params);
+        assert!(glsl.contains("const vec3 GAIN = vec3(1.0, 1.5, 2.0);"));
+        assert!(glsl.contains("const float GAMMA = 2.0;"));
+    }
 }
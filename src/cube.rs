@@ -1,6 +1,6 @@
 use std::io::{BufWriter, Write};
 
-use crate::Lut3dLinear;
+use crate::{Lut3dLinear, LutError};
 #[cfg(test)]
 use indoc::indoc;
 
@@ -23,6 +23,8 @@ impl From<&Lut3dLinear> for CubeLut3d {
     fn from(lut: &Lut3dLinear) -> Self {
         let data = lut.data.chunks(4).map(|c| (c[0], c[1], c[2])).collect();
 
+        // The domain describes the input range, which is always 0.0 to 1.0 for a Lut3dLinear.
+        // Output values outside this range like log encoded LUTs are written as is.
         CubeLut3d::new(
             "".into(),
             lut.size as u8,
@@ -33,25 +35,142 @@ impl From<&Lut3dLinear> for CubeLut3d {
     }
 }
 
+/// A 1D .cube LUT with a separate curve for each RGB channel like a tone curve.
+#[derive(Debug, PartialEq)]
+pub struct CubeLut1d {
+    pub title: String,
+    /// The RGB output values for evenly spaced inputs from `0.0` to `1.0`.
+    pub data: Vec<(f32, f32, f32)>,
+}
+
+impl CubeLut1d {
+    /// Writes the cube file with the `LUT_1D_SIZE` keyword and the default domain.
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let mut file = BufWriter::new(writer);
+        writeln!(&mut file, "#Created by: smush_lut.exe")?;
+        let title = escape_title(&self.title);
+        writeln!(&mut file, "TITLE \"{title}\"")?;
+        writeln!(&mut file)?;
+
+        writeln!(&mut file, "#LUT Size")?;
+        writeln!(&mut file, "LUT_1D_SIZE {}", self.data.len())?;
+        writeln!(&mut file)?;
+
+        writeln!(&mut file, "#LUT data points")?;
+        for (r, g, b) in &self.data {
+            writeln!(&mut file, "{r} {g} {b}")?;
+        }
+
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// The encoding of the RGB values stored in a .cube file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CubeColorSpace {
+    /// Use the values as is without any conversion.
+    #[default]
+    Data,
+    /// Values are sRGB encoded and are converted to linear.
+    Srgb,
+    /// Values are linear and are converted to sRGB encoded values.
+    Linear,
+}
+
+/// Formatting options for the data points when writing a [CubeLut3d].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeWriteOptions {
+    /// The whitespace between the values for each data point like `" "` or `"\t"`.
+    /// This must not be empty or contain line breaks.
+    pub separator: String,
+    /// Right align each value to the width of the longest value so the data forms columns.
+    pub align: bool,
+    /// The characters at the end of each line like `"\r\n"` for loaders that require Windows line endings.
+    /// This must be `"\n"` or `"\r\n"`.
+    pub line_ending: String,
+}
+
+impl Default for CubeWriteOptions {
+    fn default() -> Self {
+        Self {
+            separator: " ".into(),
+            align: false,
+            line_ending: "\n".into(),
+        }
+    }
+}
+
+impl CubeWriteOptions {
+    // Check that the output can still be parsed by CubeLut3d::from_text.
+    fn validate(&self) -> std::io::Result<()> {
+        let invalid = |message| {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                message,
+            ))
+        };
+        if self.separator.is_empty()
+            || !self
+                .separator
+                .chars()
+                .all(|c| c.is_whitespace() && c != '\n' && c != '\r')
+        {
+            return invalid("The separator must be whitespace without line breaks.");
+        }
+        if !matches!(self.line_ending.as_str(), "\n" | "\r\n") {
+            return invalid("The line ending must be \"\\n\" or \"\\r\\n\".");
+        }
+        Ok(())
+    }
+}
+
 impl CubeLut3d {
     pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.write_with_options(writer, &CubeWriteOptions::default())
+    }
+
+    /// Writes the cube file like [CubeLut3d::write] using `options` to format the data points.
+    /// Returns an [std::io::ErrorKind::InvalidInput] error for options that wouldn't produce a valid cube file.
+    pub fn write_with_options<W: Write>(
+        &self,
+        writer: &mut W,
+        options: &CubeWriteOptions,
+    ) -> std::io::Result<()> {
+        options.validate()?;
+
         let mut file = BufWriter::new(writer);
-        file.write_all(b"#Created by: smush_lut.exe\n")?;
-        writeln!(&mut file, "TITLE \"{}\"", self.title)?;
-        file.write_all(b"\n")?;
+        let newline = &options.line_ending;
+        write!(&mut file, "#Created by: smush_lut.exe{newline}")?;
+        let title = escape_title(&self.title);
+        write!(&mut file, "TITLE \"{title}\"{newline}{newline}")?;
 
-        file.write_all(b"#LUT Size\n")?;
-        writeln!(&mut file, "LUT_3D_SIZE {}", self.size)?;
-        file.write_all(b"\n")?;
+        write!(&mut file, "#LUT Size{newline}")?;
+        write!(&mut file, "LUT_3D_SIZE {}{newline}{newline}", self.size)?;
 
-        file.write_all(b"#data domain\n")?;
-        file.write_all(b"DOMAIN_MIN 0.0 0.0 0.0\n")?;
-        file.write_all(b"DOMAIN_MAX 1.0 1.0 1.0\n")?;
-        file.write_all(b"\n")?;
+        write!(&mut file, "#data domain{newline}")?;
+        let (r, g, b) = self.domain_min;
+        write!(&mut file, "DOMAIN_MIN {r:?} {g:?} {b:?}{newline}")?;
+        let (r, g, b) = self.domain_max;
+        write!(&mut file, "DOMAIN_MAX {r:?} {g:?} {b:?}{newline}{newline}")?;
 
-        file.write_all(b"#LUT data points\n")?;
+        write!(&mut file, "#LUT data points{newline}")?;
+        let width = if options.align {
+            self.data
+                .iter()
+                .flat_map(|(r, g, b)| [r, g, b])
+                .map(|f| f.to_string().len())
+                .max()
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        let separator = &options.separator;
         for (r, g, b) in &self.data {
-            writeln!(&mut file, "{r} {g} {b}")?
+            write!(
+                &mut file,
+                "{r:>width$}{separator}{g:>width$}{separator}{b:>width$}{newline}"
+            )?
         }
 
         file.flush()?;
@@ -75,7 +194,67 @@ impl CubeLut3d {
         }
     }
 
-    pub fn from_text(text: &str) -> Result<CubeLut3d, &'static str> {
+    /// Sets the domain to the minimum and maximum of the data for each channel.
+    /// This is useful for LUTs with values outside the default domain of `0.0` to `1.0`.
+    /// The domain is unchanged if there is no data.
+    pub fn auto_domain(&mut self) {
+        if self.data.is_empty() {
+            return;
+        }
+
+        let mut min = (f32::MAX, f32::MAX, f32::MAX);
+        let mut max = (f32::MIN, f32::MIN, f32::MIN);
+        for (r, g, b) in &self.data {
+            min = (min.0.min(*r), min.1.min(*g), min.2.min(*b));
+            max = (max.0.max(*r), max.1.max(*g), max.2.max(*b));
+        }
+
+        self.domain_min = min;
+        self.domain_max = max;
+    }
+
+    /// Checks that every data point is within the range from `domain_min` to `domain_max` for each channel.
+    /// Values outside the domain often indicate an incorrectly authored or out of gamut LUT.
+    /// Returns the first data point outside the domain as an error.
+    pub fn validate_domain(&self) -> Result<(), LutError> {
+        let (min, max) = (self.domain_min, self.domain_max);
+        for (index, &(r, g, b)) in self.data.iter().enumerate() {
+            let in_domain = |value: f32, min: f32, max: f32| value >= min && value <= max;
+            if !(in_domain(r, min.0, max.0)
+                && in_domain(g, min.1, max.1)
+                && in_domain(b, min.2, max.2))
+            {
+                return Err(LutError::OutOfDomain {
+                    index,
+                    value: (r, g, b),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a cube file from its raw bytes.
+    /// Invalid UTF-8 like Latin-1 encoded titles is replaced instead of failing,
+    /// since only the keywords and data need to be valid text.
+    pub fn from_bytes(bytes: &[u8]) -> Result<CubeLut3d, LutError> {
+        Self::from_text(&String::from_utf8_lossy(bytes))
+    }
+
+    /// Parses a cube file by memory mapping the file at `path`.
+    /// This avoids copying the entire file into memory for very large LUTs.
+    /// Valid UTF-8 text is parsed directly from the mapped bytes.
+    #[cfg(feature = "mmap")]
+    pub fn read_mmap<P: AsRef<std::path::Path>>(path: P) -> Result<CubeLut3d, LutError> {
+        let file = std::fs::File::open(path)?;
+        // Safety: The file must not be modified by another process while the map is in use.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Self::from_bytes(&mmap)
+    }
+
+    pub fn from_text(text: &str) -> Result<CubeLut3d, LutError> {
+        // Some editors on Windows save files with a UTF-8 byte order mark.
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
         // Skip lines with "#" to ignore comments.
         // Trim each line because the spec allows for leading/trailing whitespace.
         let lines: Vec<&str> = text
@@ -84,6 +263,13 @@ impl CubeLut3d {
             .filter(|s| !s.starts_with('#') && !s.is_empty())
             .collect();
 
+        // Report empty files separately from files with keywords but no data points.
+        if lines.is_empty() {
+            return Err(LutError::Parse(
+                "The file is empty or only contains comments.".into(),
+            ));
+        }
+
         let mut size: Option<u8> = Option::None;
 
         // Use the default values if not specified.
@@ -91,77 +277,120 @@ impl CubeLut3d {
         let mut domain_min = (0f32, 0f32, 0f32);
         let mut domain_max = (1f32, 1f32, 1f32);
 
-        let mut data_starting_line: Option<usize> = Option::None;
+        let mut data_lines = Vec::new();
 
         // Keywords can appear in any order.
-        for (i, line) in lines.iter().enumerate() {
-            let mut parts = line.split_whitespace();
-            match parts.next() {
-                Some("TITLE") => {
-                    // The title is within double quotes, so just grab the middle part.
-                    title = line
-                        .split('"')
-                        .nth(1)
-                        .ok_or("Missing value for TITLE.")?
-                        .into();
+        // Some files place keywords after the data, so check every line.
+        for line in &lines {
+            let (keyword, values) = split_keyword(line);
+            let mut parts = values.split_whitespace();
+            match keyword {
+                "TITLE" => {
+                    title = parse_title(line)
+                        .ok_or_else(|| LutError::Parse("Missing value for TITLE.".into()))?;
                 }
-                Some("LUT_3D_SIZE") => {
+                "LUT_3D_SIZE" => {
                     if let Some(size_text) = parts.next() {
                         size = size_text.parse().ok()
                     }
                 }
-                Some("DOMAIN_MIN") => {
-                    let values: Vec<f32> = parts
-                        .take(3)
-                        .filter_map(|f| f.parse::<f32>().ok())
-                        .collect();
-                    // TODO: This may fail.
-                    domain_min = (values[0], values[1], values[2])
-                }
-                Some("DOMAIN_MAX") => {
-                    let values: Vec<f32> = parts
-                        .take(3)
-                        .filter_map(|f| f.parse::<f32>().ok())
-                        .collect();
-                    // TODO: This may fail.
-                    domain_max = (values[0], values[1], values[2])
+                "DOMAIN_MIN" => {
+                    domain_min = parse_rgb(values).ok_or_else(|| {
+                        LutError::Parse("Expected 3 values for DOMAIN_MIN.".into())
+                    })?;
                 }
-                _ => {
-                    // The data is listed after all keyword lines.
-                    data_starting_line = Some(i);
-                    break;
+                "DOMAIN_MAX" => {
+                    domain_max = parse_rgb(values).ok_or_else(|| {
+                        LutError::Parse("Expected 3 values for DOMAIN_MAX.".into())
+                    })?;
                 }
+                _ => data_lines.push(*line),
             }
         }
 
-        let size = size.ok_or("Failed to parse LUT_3D_SIZE.")?;
-
-        let parse_rgb = |s: &str| {
-            let mut parts = s.split_whitespace();
-            let r: f32 = parts.next()?.parse().ok()?;
-            let g: f32 = parts.next()?.parse().ok()?;
-            let b: f32 = parts.next()?.parse().ok()?;
-            Some((r, g, b))
-        };
+        let size = size.ok_or_else(|| LutError::Parse("Failed to parse LUT_3D_SIZE.".into()))?;
 
         // Parse "0 0 1\n1 0 0..." into a single vector.
-        let data_starting_line = data_starting_line.ok_or("Failed to find data points.")?;
-        let data: Vec<(f32, f32, f32)> = lines[data_starting_line..]
+        // Check the parsed values so that files with only unrecognized keywords and no data
+        // report the same error as files without any data lines.
+        let data: Vec<(f32, f32, f32)> = data_lines.iter().filter_map(|s| parse_rgb(s)).collect();
+        if data.is_empty() {
+            return Err(LutError::Parse("Failed to find data points.".into()));
+        }
+
+        // Rust also parses values like "inf" or "NaN", which would break sampling.
+        if data
             .iter()
-            .filter_map(|s| parse_rgb(s))
-            .collect();
+            .any(|(r, g, b)| !r.is_finite() || !g.is_finite() || !b.is_finite())
+        {
+            return Err(LutError::Parse("Data points must be finite.".into()));
+        }
 
-        if data.len() != (size as usize).pow(3) {
-            return Err("Data point count does not agree with LUT_3D_SIZE.");
+        // Distinguish truncated files from files with extra data.
+        let expected = (size as usize).pow(3);
+        if data.len() < expected {
+            return Err(LutError::TooFewDataPoints {
+                size,
+                expected,
+                actual: data.len(),
+            });
+        }
+        if data.len() > expected {
+            return Err(LutError::TooManyDataPoints {
+                size,
+                expected,
+                actual: data.len(),
+            });
         }
 
-        // TODO: Make sure the size and the actual data length match.
         // TODO: Size must be greater than 2.
         let cube = CubeLut3d::new(title, size, domain_min, domain_max, data);
         Ok(cube)
     }
 }
 
+// Split a line like "LUT_3D_SIZE 33" or "LUT_3D_SIZE=33 # comment" into the keyword and values.
+// Trailing comments are removed from the values.
+fn split_keyword(line: &str) -> (&str, &str) {
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let (keyword, values) = line.split_at(end);
+    let values = values.trim_start().trim_start_matches('=');
+    let values = values.split('#').next().unwrap_or_default();
+    (keyword, values)
+}
+
+// Parse the first three values of a line like "0 0.5 1".
+fn parse_rgb(values: &str) -> Option<(f32, f32, f32)> {
+    let mut parts = values.split_whitespace();
+    let r = parts.next()?.parse().ok()?;
+    let g = parts.next()?.parse().ok()?;
+    let b = parts.next()?.parse().ok()?;
+    Some((r, g, b))
+}
+
+// Escape backslashes before quotes so a trailing \ isn't read as an escaped quote.
+fn escape_title(title: &str) -> String {
+    title.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn parse_title(line: &str) -> Option<String> {
+    // The title is within double quotes, so just grab the middle part.
+    // Quotes and backslashes within the title are escaped like \" and \\.
+    // Other backslashes are kept as is for titles from other software like C:\looks.
+    let mut chars = line.split_once('"')?.1.chars().peekable();
+    let mut title = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some('"' | '\\')) => title.extend(chars.next()),
+            '"' => break,
+            _ => title.push(c),
+        }
+    }
+    Some(title)
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::{Cursor, Read, Seek, SeekFrom};
@@ -278,11 +507,94 @@ mod tests {
         );
     }
 
+    #[test]
+    fn create_from_text_no_trailing_newline() {
+        let text = "LUT_3D_SIZE 2\n0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1";
+        let cube = CubeLut3d::from_text(text).unwrap();
+        assert_eq!(cube.size, 2);
+        assert_eq!(cube.data.len(), 8);
+        assert_eq!(cube.data[7], (1f32, 1f32, 1f32));
+    }
+
+    #[test]
+    fn create_from_text_bom_crlf_no_trailing_newline() {
+        let text = "\u{feff}TITLE \"a\"\r\nLUT_3D_SIZE 2\r\n0 0 0\r\n1 0 0\r\n0 1 0\r\n1 1 0\r\n0 0 1\r\n1 0 1\r\n0 1 1\r\n1 1 1";
+        let cube = CubeLut3d::from_text(text).unwrap();
+        assert_eq!(cube.title, "a");
+        assert_eq!(cube.size, 2);
+        assert_eq!(cube.data.len(), 8);
+        assert_eq!(cube.data[7], (1f32, 1f32, 1f32));
+    }
+
     #[test]
     fn create_from_text_missing_size() {
         let text = "bad cube file";
         let cube = CubeLut3d::from_text(text);
-        assert_eq!(cube, Err("Failed to parse LUT_3D_SIZE."));
+        assert!(matches!(cube, Err(LutError::Parse(e)) if e == "Failed to parse LUT_3D_SIZE."));
+    }
+
+    #[test]
+    fn create_from_text_size_equals() {
+        let text = indoc! {r#"
+            LUT_3D_SIZE=2
+            DOMAIN_MIN = 0 0 0
+            DOMAIN_MAX=1 1 1
+            0 0 0
+            1 0 0
+            0 .75 0
+            1 .75 0
+            0 .25 1
+            1 .25 1
+            0 1 1
+            1 1 1
+        "#};
+        let cube = CubeLut3d::from_text(text).unwrap();
+        assert_eq!(2, cube.size);
+        assert_eq!((0.0, 0.0, 0.0), cube.domain_min);
+        assert_eq!((1.0, 1.0, 1.0), cube.domain_max);
+        assert_eq!(8, cube.data.len());
+    }
+
+    #[test]
+    fn create_from_text_size_trailing_comment() {
+        let text = indoc! {r#"
+            TITLE "size # comment"
+            LUT_3D_SIZE 2 # generated
+            DOMAIN_MAX 1 1 1#max
+            0 0 0
+            1 0 0
+            0 .75 0
+            1 .75 0
+            0 .25 1
+            1 .25 1
+            0 1 1
+            1 1 1
+        "#};
+        let cube = CubeLut3d::from_text(text).unwrap();
+        assert_eq!("size # comment", cube.title);
+        assert_eq!(2, cube.size);
+        assert_eq!((1.0, 1.0, 1.0), cube.domain_max);
+    }
+
+    #[test]
+    fn create_from_text_whitespace_only() {
+        let cube = CubeLut3d::from_text(" \n\t\n\r\n  \n");
+        assert!(
+            matches!(cube, Err(LutError::Parse(e)) if e == "The file is empty or only contains comments.")
+        );
+    }
+
+    #[test]
+    fn create_from_text_comments_only() {
+        let text = indoc! {r#"
+            # Created by an editor
+
+            # LUT_3D_SIZE 2
+        "#};
+        let cube = CubeLut3d::from_text(text);
+        assert!(
+            matches!(cube, Err(LutError::Parse(e)) if e == "The file is empty or only contains comments.")
+        );
     }
 
     #[test]
@@ -292,7 +604,29 @@ mod tests {
             LUT_3D_SIZE 2
         "#};
         let cube = CubeLut3d::from_text(text);
-        assert_eq!(cube, Err("Failed to find data points."));
+        assert!(matches!(cube, Err(LutError::Parse(e)) if e == "Failed to find data points."));
+    }
+
+    #[test]
+    fn create_from_text_size_then_comments() {
+        let text = indoc! {r#"
+            LUT_3D_SIZE 2
+            # 0 0 0
+            # 1 1 1
+        "#};
+        let cube = CubeLut3d::from_text(text);
+        assert!(matches!(cube, Err(LutError::Parse(e)) if e == "Failed to find data points."));
+    }
+
+    #[test]
+    fn create_from_text_unrecognized_keywords_no_data() {
+        let text = indoc! {r#"
+            LUT_3D_SIZE 2
+            LUT_3D_INPUT_RANGE 0.0 1.0
+            # comment
+        "#};
+        let cube = CubeLut3d::from_text(text);
+        assert!(matches!(cube, Err(LutError::Parse(e)) if e == "Failed to find data points."));
     }
 
     #[test]
@@ -310,7 +644,7 @@ mod tests {
             1 1 1
         "#};
         let cube = CubeLut3d::from_text(text);
-        assert_eq!(cube, Err("Failed to parse LUT_3D_SIZE."));
+        assert!(matches!(cube, Err(LutError::Parse(e)) if e == "Failed to parse LUT_3D_SIZE."));
     }
 
     #[test]
@@ -328,10 +662,56 @@ mod tests {
             1
         "#};
         let cube = CubeLut3d::from_text(text);
-        assert_eq!(
+        assert!(matches!(
             cube,
-            Err("Data point count does not agree with LUT_3D_SIZE.")
-        );
+            Err(LutError::TooFewDataPoints {
+                size: 2,
+                expected: 8,
+                actual: 6
+            })
+        ));
+    }
+
+    #[test]
+    fn create_from_text_extra_rgb_triple() {
+        let text = indoc! {r#"
+            LUT_3D_SIZE 2
+            0 0 0
+            1 0 0
+            0 .75 0
+            1 .75 0
+            0 .25 1
+            1 .25 1
+            0 1 1
+            1 1 1
+            1 1 1
+        "#};
+        let cube = CubeLut3d::from_text(text);
+        assert!(matches!(
+            cube,
+            Err(LutError::TooManyDataPoints {
+                size: 2,
+                expected: 8,
+                actual: 9
+            })
+        ));
+    }
+
+    #[test]
+    fn create_from_text_nan() {
+        let text = indoc! {r#"
+            LUT_3D_SIZE 2
+            0 0 0
+            1 0 0
+            0 .75 0
+            1 NaN 0
+            0 .25 1
+            1 .25 1
+            0 1 1
+            1 1 inf
+        "#};
+        let cube = CubeLut3d::from_text(text);
+        assert!(matches!(cube, Err(LutError::Parse(e)) if e == "Data points must be finite."));
     }
 
     #[test]
@@ -350,7 +730,7 @@ mod tests {
             1 1 1
         "#};
         let cube = CubeLut3d::from_text(text);
-        assert_eq!(cube, Err("Missing value for TITLE."));
+        assert!(matches!(cube, Err(LutError::Parse(e)) if e == "Missing value for TITLE."));
     }
 
     #[test]
@@ -396,6 +776,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn create_from_text_domain_missing_values() {
+        for (keyword, domain) in [
+            ("DOMAIN_MIN", "DOMAIN_MIN 0 0"),
+            ("DOMAIN_MAX", "DOMAIN_MAX 1 1 a"),
+            ("DOMAIN_MAX", "DOMAIN_MAX"),
+        ] {
+            let text = format!("LUT_3D_SIZE 2\n{}\n0 0 0\n", domain);
+            let cube = CubeLut3d::from_text(&text);
+            assert!(
+                matches!(cube, Err(LutError::Parse(e)) if e == format!("Expected 3 values for {}.", keyword))
+            );
+        }
+    }
+
     #[test]
     fn create_from_text_title_spaces() {
         let text = indoc! {r#"
@@ -430,6 +825,117 @@ mod tests {
         );
     }
 
+    #[test]
+    fn create_from_text_keywords_after_data() {
+        let text = indoc! {r#"
+            0 0 0
+            1 0 0
+            LUT_3D_SIZE 2
+            0 .75 0
+            1 .75 0
+            TITLE "lut1"
+            0 .25 1
+            1 .25 1
+            DOMAIN_MAX 1 2 3
+            0 1 1
+            1 1 1
+        "#};
+        let cube = CubeLut3d::from_text(text).unwrap();
+        assert_eq!(cube.title, "lut1");
+        assert_eq!(cube.size, 2);
+        assert_eq!(cube.domain_min, (0f32, 0f32, 0f32));
+        assert_eq!(cube.domain_max, (1f32, 2f32, 3f32));
+        assert_eq!(
+            cube.data,
+            vec![
+                (0f32, 0f32, 0f32),
+                (1f32, 0f32, 0f32),
+                (0f32, 0.75f32, 0f32),
+                (1f32, 0.75f32, 0f32),
+                (0f32, 0.25f32, 1f32),
+                (1f32, 0.25f32, 1f32),
+                (0f32, 1f32, 1f32),
+                (1f32, 1f32, 1f32)
+            ]
+        );
+    }
+
+    #[test]
+    fn create_from_bytes_non_utf8_title() {
+        let mut bytes = b"TITLE \"caf".to_vec();
+        // Latin-1 encoding for 'é'.
+        bytes.push(0xE9);
+        bytes.extend_from_slice(b"\"\nLUT_3D_SIZE 2\n");
+        bytes.extend_from_slice("0 0 0\n".repeat(7).as_bytes());
+        bytes.extend_from_slice(b"1 1 1");
+
+        let cube = CubeLut3d::from_bytes(&bytes).unwrap();
+        assert_eq!(cube.title, "caf\u{FFFD}");
+        assert_eq!(cube.size, 2);
+        assert_eq!(cube.data[7], (1f32, 1f32, 1f32));
+    }
+
+    #[test]
+    fn create_from_text_title_escaped_quotes() {
+        let text = indoc! {r#"
+            LUT_3D_SIZE 2
+            TITLE "my \"look\" C:\luts"
+            0 0 0
+            1 0 0
+            0 .75 0
+            1 .75 0
+            0 .25 1
+            1 .25 1
+            0 1 1
+            1 1 1
+        "#};
+        let cube = CubeLut3d::from_text(text).unwrap();
+        assert_eq!(cube.title, r#"my "look" C:\luts"#);
+    }
+
+    #[test]
+    fn read_write_title_escaped_quotes() {
+        let cube = CubeLut3d::new(
+            r#"my "look""#.into(),
+            2,
+            (0f32, 0f32, 0f32),
+            (1f32, 1f32, 1f32),
+            vec![(0.5f32, 0.5f32, 0.5f32); 8],
+        );
+
+        let mut c = Cursor::new(Vec::new());
+        cube.write(&mut c).unwrap();
+
+        let text = get_string(&mut c).unwrap();
+        assert!(text.contains(r#"TITLE "my \"look\"""#));
+        assert_eq!(cube, CubeLut3d::from_text(&text).unwrap());
+    }
+
+    #[test]
+    fn read_write_title_trailing_backslash() {
+        let cube = CubeLut3d::new(
+            r"C:\looks\".into(),
+            2,
+            (0f32, 0f32, 0f32),
+            (1f32, 1f32, 1f32),
+            vec![(0.5f32, 0.5f32, 0.5f32); 8],
+        );
+
+        let mut c = Cursor::new(Vec::new());
+        cube.write(&mut c).unwrap();
+
+        let text = get_string(&mut c).unwrap();
+        assert!(text.contains(r#"TITLE "C:\\looks\\""#));
+        assert_eq!(cube, CubeLut3d::from_text(&text).unwrap());
+    }
+
+    #[test]
+    fn create_from_text_title_unescaped_backslash() {
+        let text = "TITLE \"C:\\looks\\sepia\"\nLUT_3D_SIZE 2\n0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+        let cube = CubeLut3d::from_text(text).unwrap();
+        assert_eq!(r"C:\looks\sepia", cube.title);
+    }
+
     #[test]
     fn create_from_name_size_data() {
         let cube = CubeLut3d::new(
@@ -446,6 +952,78 @@ mod tests {
         assert_eq!(cube.data, vec![(1f32, 1f32, 1f32); 8]);
     }
 
+    #[test]
+    fn auto_domain_extended_range() {
+        let mut cube = CubeLut3d::new(
+            "cube".into(),
+            2,
+            (0f32, 0f32, 0f32),
+            (1f32, 1f32, 1f32),
+            vec![(0.5f32, 0.25f32, 0.5f32); 8],
+        );
+        cube.data[1] = (1.5, 0.0, 0.75);
+        cube.data[2] = (-0.5, 1.0, 0.5);
+
+        cube.auto_domain();
+        assert_eq!(cube.domain_min, (-0.5f32, 0f32, 0.5f32));
+        assert_eq!(cube.domain_max, (1.5f32, 1f32, 0.75f32));
+    }
+
+    #[test]
+    fn validate_domain_in_range() {
+        let cube = CubeLut3d::from(Lut3dLinear::identity());
+        assert!(cube.validate_domain().is_ok());
+    }
+
+    #[test]
+    fn validate_domain_out_of_range() {
+        let mut cube = CubeLut3d::new(
+            "cube".into(),
+            2,
+            (0f32, 0f32, 0f32),
+            (1f32, 1f32, 1f32),
+            vec![(0.5f32, 0.5f32, 0.5f32); 8],
+        );
+        cube.data[3] = (0.5, 1.25, 0.5);
+
+        let error = cube.validate_domain().unwrap_err();
+        assert!(matches!(
+            error,
+            LutError::OutOfDomain {
+                index: 3,
+                value: (0.5, 1.25, 0.5)
+            }
+        ));
+        assert_eq!(
+            "Data point 3 with value (0.5, 1.25, 0.5) is outside the domain.",
+            error.to_string()
+        );
+
+        // Extending the domain should include the value.
+        cube.auto_domain();
+        assert!(cube.validate_domain().is_ok());
+    }
+
+    #[test]
+    fn write_auto_domain() {
+        let mut cube = CubeLut3d::new(
+            "cube".into(),
+            2,
+            (0f32, 0f32, 0f32),
+            (1f32, 1f32, 1f32),
+            vec![(0.5f32, 0.5f32, 1.5f32); 8],
+        );
+        cube.auto_domain();
+
+        let mut c = Cursor::new(Vec::new());
+        cube.write(&mut c).unwrap();
+
+        let text = get_string(&mut c).unwrap();
+        assert!(text.contains("DOMAIN_MIN 0.5 0.5 1.5\n"));
+        assert!(text.contains("DOMAIN_MAX 0.5 0.5 1.5\n"));
+        assert_eq!(cube, CubeLut3d::from_text(&text).unwrap());
+    }
+
     #[test]
     fn read_write() {
         // Make sure the parser and writer are compatible.
@@ -505,4 +1083,128 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn write_1d() {
+        let cube = CubeLut1d {
+            title: "curve".into(),
+            data: vec![(0.0, 0.0, 0.0), (0.25, 0.5, 0.75), (1.0, 1.0, 1.0)],
+        };
+
+        let mut c = Cursor::new(Vec::new());
+        cube.write(&mut c).unwrap();
+
+        let expected = indoc! {r#"
+            #Created by: smush_lut.exe
+            TITLE "curve"
+            
+            #LUT Size
+            LUT_1D_SIZE 3
+            
+            #LUT data points
+            0 0 0
+            0.25 0.5 0.75
+            1 1 1
+        "#};
+
+        assert_eq!(expected, get_string(&mut c).unwrap());
+    }
+
+    #[test]
+    fn write_aligned_tabs() {
+        let mut cube = CubeLut3d::new(
+            "cube".into(),
+            2,
+            (0f32, 0f32, 0f32),
+            (1f32, 1f32, 1f32),
+            vec![(1f32, 0.5f32, 0f32); 8],
+        );
+        cube.data[7] = (-0.25, 0.125, 1.0);
+
+        let options = CubeWriteOptions {
+            separator: "\t".into(),
+            align: true,
+            ..Default::default()
+        };
+        let mut c = Cursor::new(Vec::new());
+        cube.write_with_options(&mut c, &options).unwrap();
+
+        let text = get_string(&mut c).unwrap();
+        let data_lines: Vec<_> = text.lines().rev().take(2).collect();
+        assert_eq!("-0.25\t0.125\t    1", data_lines[0]);
+        assert_eq!("    1\t  0.5\t    0", data_lines[1]);
+        assert_eq!(cube, CubeLut3d::from_text(&text).unwrap());
+    }
+
+    #[test]
+    fn write_crlf() {
+        let cube = CubeLut3d::new(
+            "cube".into(),
+            2,
+            (0f32, 0f32, 0f32),
+            (1f32, 1f32, 1f32),
+            vec![(1f32, 0.5f32, 0f32); 8],
+        );
+
+        let options = CubeWriteOptions {
+            line_ending: "\r\n".into(),
+            ..Default::default()
+        };
+        let mut c = Cursor::new(Vec::new());
+        cube.write_with_options(&mut c, &options).unwrap();
+
+        let text = get_string(&mut c).unwrap();
+        assert!(text.ends_with("1 0.5 0\r\n"));
+        assert_eq!(text.matches('\n').count(), text.matches("\r\n").count());
+        assert_eq!(cube, CubeLut3d::from_text(&text).unwrap());
+
+        // Only the line endings should change.
+        let mut c = Cursor::new(Vec::new());
+        cube.write(&mut c).unwrap();
+        assert_eq!(get_string(&mut c).unwrap(), text.replace("\r\n", "\n"));
+    }
+
+    #[test]
+    fn write_invalid_options() {
+        let cube = CubeLut3d::from(&Lut3dLinear::identity());
+        for (separator, line_ending) in [
+            (",", "\n"),
+            ("", "\n"),
+            (" \n", "\n"),
+            (" ", ""),
+            (" ", " "),
+            (" ", "\r"),
+        ] {
+            let options = CubeWriteOptions {
+                separator: separator.into(),
+                line_ending: line_ending.into(),
+                ..Default::default()
+            };
+            let mut c = Cursor::new(Vec::new());
+            let error = cube.write_with_options(&mut c, &options).unwrap_err();
+            assert_eq!(std::io::ErrorKind::InvalidInput, error.kind());
+            assert!(c.get_ref().is_empty());
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn read_mmap_matches_from_text() {
+        let lut = Lut3dLinear::from_matrix(
+            33,
+            [[0.9, 0.1, 0.0], [0.0, 0.8, 0.2], [0.1, 0.0, 0.9]],
+            [0.0; 3],
+        );
+        let mut text = Vec::new();
+        CubeLut3d::from(&lut).write(&mut text).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lut.cube");
+        std::fs::write(&path, &text).unwrap();
+
+        assert_eq!(
+            CubeLut3d::from_text(std::str::from_utf8(&text).unwrap()).unwrap(),
+            CubeLut3d::read_mmap(&path).unwrap()
+        );
+    }
 }
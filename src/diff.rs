@@ -0,0 +1,186 @@
+use crate::{color_correction::linear, Lut3dLinear};
+
+/// Metrics for the difference between the RGB values of two LUTs.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LutDifference {
+    /// The mean absolute difference.
+    pub mean: f32,
+    /// The largest absolute difference.
+    pub max: f32,
+    /// The root mean squared difference.
+    pub rmse: f32,
+    /// The peak signal to noise ratio in decibels assuming a peak value of `1.0`.
+    /// This is infinite for identical LUTs.
+    pub psnr: f32,
+}
+
+/// Calculates the difference between the RGB values of `a` and `b` or `None` if the sizes differ.
+/// Resample one of the LUTs with [Lut3dLinear::resample] to compare LUTs of different sizes.
+pub fn difference(a: &Lut3dLinear, b: &Lut3dLinear) -> Option<LutDifference> {
+    if a.size != b.size || a.data.len() != b.data.len() {
+        return None;
+    }
+
+    let mut sum = 0.0;
+    let mut sum_squared = 0.0;
+    let mut max = 0.0f32;
+    let mut count = 0;
+    for (rgba_a, rgba_b) in a.data.chunks_exact(4).zip(b.data.chunks_exact(4)) {
+        for c in 0..3 {
+            let diff = (rgba_a[c] - rgba_b[c]).abs();
+            sum += diff;
+            sum_squared += diff * diff;
+            max = max.max(diff);
+            count += 1;
+        }
+    }
+
+    let count = count.max(1) as f32;
+    let mse = sum_squared / count;
+    Some(LutDifference {
+        mean: sum / count,
+        max,
+        rmse: mse.sqrt(),
+        psnr: -10.0 * mse.log10(),
+    })
+}
+
+/// Perceptual differences between the colors of two LUTs using the CIE76 color difference in CIELAB.
+/// A difference of about `2.3` is just noticeable.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DeltaE {
+    /// The mean color difference.
+    pub mean: f32,
+    /// The largest color difference.
+    pub max: f32,
+}
+
+/// Calculates the perceptual difference between the colors of `a` and `b` or `None` if the sizes differ.
+/// RGB values are assumed to be sRGB encoded with a D65 white point.
+pub fn delta_e(a: &Lut3dLinear, b: &Lut3dLinear) -> Option<DeltaE> {
+    if a.size != b.size || a.data.len() != b.data.len() {
+        return None;
+    }
+
+    let mut sum = 0.0;
+    let mut max = 0.0f32;
+    let mut count = 0;
+    for (rgba_a, rgba_b) in a.data.chunks_exact(4).zip(b.data.chunks_exact(4)) {
+        let lab_a = srgb_to_lab([rgba_a[0], rgba_a[1], rgba_a[2]]);
+        let lab_b = srgb_to_lab([rgba_b[0], rgba_b[1], rgba_b[2]]);
+        let diff = lab_a
+            .iter()
+            .zip(lab_b)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f32>()
+            .sqrt();
+        sum += diff;
+        max = max.max(diff);
+        count += 1;
+    }
+
+    Some(DeltaE {
+        mean: sum / count.max(1) as f32,
+        max,
+    })
+}
+
+// https://en.wikipedia.org/wiki/CIELAB_color_space#From_CIEXYZ_to_CIELAB
+fn srgb_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(linear);
+
+    // Linear sRGB to XYZ relative to the D65 white point.
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        let delta: f32 = 6.0 / 29.0;
+        if t > delta.powi(3) {
+            t.cbrt()
+        } else {
+            t / (3.0 * delta * delta) + 4.0 / 29.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn difference_identical() {
+        let lut = Lut3dLinear::default_stage();
+        let diff = difference(&lut, &lut).unwrap();
+        assert_eq!(0.0, diff.mean);
+        assert_eq!(0.0, diff.max);
+        assert_eq!(0.0, diff.rmse);
+        assert_eq!(f32::INFINITY, diff.psnr);
+    }
+
+    #[test]
+    fn difference_offset() {
+        let a = Lut3dLinear::identity();
+        let mut b = Lut3dLinear::identity();
+        for rgba in b.data.chunks_exact_mut(4) {
+            rgba[0] += 0.1;
+        }
+
+        let diff = difference(&a, &b).unwrap();
+        assert_relative_eq!(0.1 / 3.0, diff.mean, epsilon = 0.0001);
+        assert_relative_eq!(0.1, diff.max, epsilon = 0.0001);
+        assert_relative_eq!((0.01f32 / 3.0).sqrt(), diff.rmse, epsilon = 0.0001);
+        assert_relative_eq!(24.77, diff.psnr, epsilon = 0.01);
+    }
+
+    #[test]
+    fn difference_size_mismatch() {
+        let a = Lut3dLinear::identity();
+        let b = a.resample(8);
+        assert_eq!(None, difference(&a, &b));
+    }
+
+    #[test]
+    fn delta_e_identical() {
+        let lut = Lut3dLinear::default_stage();
+        assert_eq!(
+            DeltaE {
+                mean: 0.0,
+                max: 0.0
+            },
+            delta_e(&lut, &lut).unwrap()
+        );
+    }
+
+    #[test]
+    fn delta_e_offset() {
+        let a = Lut3dLinear::identity();
+        let mut b = Lut3dLinear::identity();
+        for rgba in b.data.chunks_exact_mut(4) {
+            for value in &mut rgba[..3] {
+                *value = (*value + 0.01).min(1.0);
+            }
+        }
+
+        // A small brightness change should be barely noticeable.
+        let diff = delta_e(&a, &b).unwrap();
+        assert!(diff.mean > 0.3 && diff.mean < 2.3, "{:?}", diff);
+        assert!(diff.max < 5.0, "{:?}", diff);
+    }
+
+    #[test]
+    fn srgb_to_lab_black_white() {
+        assert_relative_eq!(0.0, srgb_to_lab([0.0; 3])[0], epsilon = 0.001);
+        let white = srgb_to_lab([1.0; 3]);
+        assert_relative_eq!(&[100.0, 0.0, 0.0][..], &white[..], epsilon = 0.05);
+    }
+
+    #[test]
+    fn delta_e_size_mismatch() {
+        let a = Lut3dLinear::identity();
+        assert_eq!(None, delta_e(&a, &a.resample(8)));
+    }
+}
@@ -0,0 +1,140 @@
+use std::io::{Read, Write};
+
+use crate::{CubeLut3d, Lut3dLinear, LutError};
+
+// The zTXt keyword for the .cube text with the full precision LUT values.
+const SOURCE_KEYWORD: &str = "smush_lut_cube";
+
+/// Writes `lut` as a PNG with the same layout as the [RgbaImage](image::RgbaImage) conversion
+/// and embeds the full precision values as .cube text in a compressed zTXt chunk.
+/// The `bit_depth` for the image data must be 8 or 16.
+pub fn write_png_with_source<W: Write>(
+    lut: &Lut3dLinear,
+    writer: W,
+    bit_depth: u32,
+) -> Result<(), LutError> {
+    let (png_depth, data) = match bit_depth {
+        8 => (png::BitDepth::Eight, lut.to_rgba()),
+        // PNG stores 16-bit values in big endian order.
+        16 => (
+            png::BitDepth::Sixteen,
+            lut.to_rgba16()
+                .iter()
+                .flat_map(|v| v.to_be_bytes())
+                .collect(),
+        ),
+        _ => {
+            return Err(LutError::UnsupportedFormat(format!(
+                "{}-bit PNG",
+                bit_depth
+            )))
+        }
+    };
+
+    let mut text = Vec::new();
+    CubeLut3d::from(lut).write(&mut text)?;
+    // The .cube text written by this library is always ASCII.
+    let text = String::from_utf8_lossy(&text).into_owned();
+
+    let mut encoder = png::Encoder::new(writer, (lut.size * lut.size) as u32, lut.size as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png_depth);
+    encoder
+        .add_ztxt_chunk(SOURCE_KEYWORD.to_string(), text)
+        .map_err(png_error)?;
+
+    let mut png_writer = encoder.write_header().map_err(png_error)?;
+    png_writer.write_image_data(&data).map_err(png_error)?;
+    png_writer.finish().map_err(png_error)?;
+    Ok(())
+}
+
+/// Reads the full precision LUT embedded by [write_png_with_source]
+/// or `None` if the PNG doesn't have embedded LUT data.
+pub fn read_png_source<R: Read>(reader: R) -> Result<Option<Lut3dLinear>, LutError> {
+    let decoder = png::Decoder::new(reader);
+    let reader = decoder
+        .read_info()
+        .map_err(|e| LutError::Parse(e.to_string()))?;
+
+    match reader
+        .info()
+        .compressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == SOURCE_KEYWORD)
+    {
+        Some(chunk) => {
+            let text = chunk
+                .get_text()
+                .map_err(|e| LutError::Parse(e.to_string()))?;
+            let cube = CubeLut3d::from_text(&text)?;
+            Ok(Some(cube.into()))
+        }
+        None => Ok(None),
+    }
+}
+
+fn png_error(e: png::EncodingError) -> LutError {
+    match e {
+        png::EncodingError::IoError(e) => LutError::Io(e),
+        e => LutError::Parse(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use std::io::Cursor;
+
+    fn lut() -> Lut3dLinear {
+        // Use values that aren't exactly representable with 8 bits.
+        Lut3dLinear::from_matrix(
+            8,
+            [[0.8, 0.15, 0.03], [0.1, 0.75, 0.1], [0.05, 0.05, 0.85]],
+            [0.011, 0.007, 0.003],
+        )
+    }
+
+    #[test]
+    fn embed_source_round_trip() {
+        let lut = lut();
+        let mut png = Vec::new();
+        write_png_with_source(&lut, &mut png, 8).unwrap();
+
+        assert_eq!(Some(lut), read_png_source(Cursor::new(&png)).unwrap());
+    }
+
+    #[test]
+    fn embed_source_image_data() {
+        let lut = lut();
+        let mut png = Vec::new();
+        write_png_with_source(&lut, &mut png, 8).unwrap();
+
+        // The image data should still be readable by other programs.
+        let img = image::load_from_memory(&png).unwrap().into_rgba8();
+        assert_eq!(image::RgbaImage::try_from(&lut).unwrap(), img);
+    }
+
+    #[test]
+    fn embed_source_16_bit() {
+        let lut = lut();
+        let mut png = Vec::new();
+        write_png_with_source(&lut, &mut png, 16).unwrap();
+
+        let img = image::load_from_memory(&png).unwrap().into_rgba16();
+        assert_eq!(lut.to_rgba16(), img.into_raw());
+        assert_eq!(Some(lut), read_png_source(Cursor::new(&png)).unwrap());
+    }
+
+    #[test]
+    fn read_source_missing() {
+        let mut png = Vec::new();
+        image::RgbaImage::try_from(&lut())
+            .unwrap()
+            .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .unwrap();
+
+        assert_eq!(None, read_png_source(Cursor::new(&png)).unwrap());
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt::Display;
+
+/// The error type for reading, writing, and converting LUTs.
+#[derive(Debug)]
+pub enum LutError {
+    /// The image or texture dimensions don't describe a valid LUT.
+    InvalidDimensions(&'static str),
+    /// The image data has fewer bytes than required for a LUT of the given size.
+    NotEnoughData {
+        size: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// The LUT data doesn't have exactly one RGBA value for each node of a LUT with the given size.
+    DataLengthMismatch {
+        size: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// The .cube file has fewer data points than required by LUT_3D_SIZE.
+    TooFewDataPoints {
+        size: u8,
+        expected: usize,
+        actual: usize,
+    },
+    /// The .cube file has more data points than required by LUT_3D_SIZE.
+    TooManyDataPoints {
+        size: u8,
+        expected: usize,
+        actual: usize,
+    },
+    /// A .cube data point is outside the range from DOMAIN_MIN to DOMAIN_MAX.
+    OutOfDomain {
+        index: usize,
+        value: (f32, f32, f32),
+    },
+    /// Text or binary data could not be parsed or serialized.
+    Parse(String),
+    /// The file uses a format or image format that isn't supported.
+    UnsupportedFormat(String),
+    /// An error from the nutexb library while reading or writing a texture.
+    Nutexb(String),
+    Io(std::io::Error),
+}
+
+impl Display for LutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LutError::InvalidDimensions(message) => write!(f, "{}", message),
+            LutError::NotEnoughData {
+                size,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Expected at least {} bytes of image data for a {}x{}x{} LUT but found {}.",
+                expected, size, size, size, actual
+            ),
+            LutError::DataLengthMismatch {
+                size,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Expected {} values for a {}x{}x{} RGBA LUT but found {}.",
+                expected, size, size, size, actual
+            ),
+            LutError::TooFewDataPoints {
+                size,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Too few data points for LUT_3D_SIZE {}. Expected {} but found {}.",
+                size, expected, actual
+            ),
+            LutError::TooManyDataPoints {
+                size,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Too many data points for LUT_3D_SIZE {}. Expected {} but found {}.",
+                size, expected, actual
+            ),
+            LutError::OutOfDomain { index, value } => write!(
+                f,
+                "Data point {} with value {:?} is outside the domain.",
+                index, value
+            ),
+            LutError::Parse(message) => write!(f, "{}", message),
+            LutError::UnsupportedFormat(format) => write!(f, "Unsupported format {}.", format),
+            LutError::Nutexb(message) => write!(f, "{}", message),
+            LutError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LutError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LutError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for LutError {
+    fn from(e: std::io::Error) -> Self {
+        LutError::Io(e)
+    }
+}
+
+// The nutexb library uses boxed errors.
+pub(crate) fn nutexb_error(e: Box<dyn std::error::Error>) -> LutError {
+    LutError::Nutexb(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_data_points() {
+        let error = LutError::TooFewDataPoints {
+            size: 2,
+            expected: 8,
+            actual: 6,
+        };
+        assert_eq!(
+            "Too few data points for LUT_3D_SIZE 2. Expected 8 but found 6.",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn io_source() {
+        let error = LutError::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(std::error::Error::source(&error).is_some());
+    }
+}
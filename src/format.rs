@@ -0,0 +1,223 @@
+use std::io::{Cursor, Read, Write};
+
+use nutexb::NutexbFile;
+
+use crate::{
+    error::nutexb_error, lut_from_nutexb, validate_nutexb_lut, CubeLut3d, Lut3dLinear, LutError,
+};
+
+/// A file format for reading and writing a [Lut3dLinear].
+/// Implement this trait and add the format to [FORMATS] to support a new file extension.
+pub trait LutFormat: Sync {
+    /// The file extensions for this format without the leading `'.'`.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Returns `true` if `bytes` look like the contents of a file in this format.
+    /// This allows detecting files with missing or incorrect extensions.
+    fn sniff(&self, _bytes: &[u8]) -> bool {
+        false
+    }
+
+    fn read(&self, reader: &mut dyn Read) -> Result<Lut3dLinear, LutError>;
+
+    fn write(&self, lut: &Lut3dLinear, writer: &mut dyn Write) -> Result<(), LutError>;
+}
+
+/// The text based .cube format.
+pub struct Cube;
+
+impl LutFormat for Cube {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["cube"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        // Every valid file has the size keyword.
+        bytes.windows(11).any(|w| w == b"LUT_3D_SIZE")
+    }
+
+    fn read(&self, reader: &mut dyn Read) -> Result<Lut3dLinear, LutError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let cube = CubeLut3d::from_bytes(&bytes)?;
+        Ok(cube.into())
+    }
+
+    fn write(&self, lut: &Lut3dLinear, mut writer: &mut dyn Write) -> Result<(), LutError> {
+        let cube = CubeLut3d::from(lut);
+        cube.write(&mut writer)?;
+        Ok(())
+    }
+}
+
+/// The swizzled 3D texture format used for the in game color grading LUT.
+pub struct Nutexb;
+
+impl LutFormat for Nutexb {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["nutexb"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        // The footer ends with a magic value followed by the version.
+        bytes.len() >= 8 && &bytes[bytes.len() - 8..bytes.len() - 4] == b" XET"
+    }
+
+    fn read(&self, reader: &mut dyn Read) -> Result<Lut3dLinear, LutError> {
+        // Parsing the footer requires seeking, so buffer the entire file.
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let nutexb = NutexbFile::read(&mut Cursor::new(bytes))
+            .map_err(|e| LutError::Nutexb(e.to_string()))?;
+        lut_from_nutexb(&nutexb)
+    }
+
+    fn write(&self, lut: &Lut3dLinear, writer: &mut dyn Write) -> Result<(), LutError> {
+        validate_nutexb_lut(lut)?;
+        let mut bytes = Cursor::new(Vec::new());
+        NutexbFile::create(lut, "color_grading_lut")
+            .and_then(|nutexb| nutexb.write(&mut bytes))
+            .map_err(nutexb_error)?;
+        writer.write_all(bytes.get_ref())?;
+        Ok(())
+    }
+}
+
+/// The supported formats in the order they are checked.
+pub static FORMATS: &[&dyn LutFormat] = &[&Cube, &Nutexb];
+
+/// The file extensions without the leading `'.'` that can be used as inputs.
+/// This includes the [FORMATS], raw .bin files, and images decoded with the image crate.
+pub fn supported_input_formats() -> &'static [&'static str] {
+    &[
+        "cube", "nutexb", "bin", "png", "bmp", "tga", "tif", "tiff", "jpg", "jpeg", "webp", "exr",
+    ]
+}
+
+/// The file extensions without the leading `'.'` that can be used as outputs.
+/// This includes the [FORMATS], raw .bin files, and lossless images encoded with the image crate.
+/// Floating point .exr images require the `openexr` feature.
+pub fn supported_output_formats() -> &'static [&'static str] {
+    #[cfg(feature = "openexr")]
+    return &[
+        "cube", "nutexb", "bin", "png", "bmp", "tga", "tif", "tiff", "exr",
+    ];
+
+    #[cfg(not(feature = "openexr"))]
+    &["cube", "nutexb", "bin", "png", "bmp", "tga", "tif", "tiff"]
+}
+
+/// Finds the format for the given file extension like `"cube"` or `None` if not supported.
+/// The comparison ignores case.
+pub fn from_extension(extension: &str) -> Option<&'static dyn LutFormat> {
+    FORMATS.iter().copied().find(|f| {
+        f.extensions()
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(extension))
+    })
+}
+
+/// Finds the format for a file with the given `extension` and contents.
+/// The extension is checked first, but formats detected from `bytes` take priority
+/// if the contents don't match the format for the extension.
+pub fn detect(extension: Option<&str>, bytes: &[u8]) -> Option<&'static dyn LutFormat> {
+    let hint = extension.and_then(from_extension);
+    match hint {
+        Some(format) if format.sniff(bytes) => Some(format),
+        _ => FORMATS.iter().copied().find(|f| f.sniff(bytes)).or(hint),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_write(extension: &str) {
+        let format = from_extension(extension).unwrap();
+
+        let lut = Lut3dLinear::identity();
+        let mut bytes = Vec::new();
+        format.write(&lut, &mut bytes).unwrap();
+
+        let new_lut = format.read(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(lut.size, new_lut.size);
+        approx::assert_relative_eq!(lut.data[..], new_lut.data[..], epsilon = 1.0 / 255.0);
+    }
+
+    #[test]
+    fn read_write_cube() {
+        read_write("cube");
+    }
+
+    #[test]
+    fn read_write_nutexb() {
+        read_write("NUTEXB");
+    }
+
+    #[test]
+    fn supported_formats() {
+        for formats in [supported_input_formats(), supported_output_formats()] {
+            for format in FORMATS {
+                for extension in format.extensions() {
+                    assert!(formats.contains(extension));
+                }
+            }
+            assert!(formats.contains(&"bin"));
+            assert!(formats.contains(&"png"));
+        }
+        assert!(supported_input_formats().contains(&"jpg"));
+        assert!(!supported_output_formats().contains(&"jpg"));
+    }
+
+    #[test]
+    fn write_nutexb_unsupported_size() {
+        let mut bytes = Vec::new();
+        let result = from_extension("nutexb")
+            .unwrap()
+            .write(&Lut3dLinear::from_fn(33, |c| c), &mut bytes);
+        assert!(matches!(result, Err(LutError::InvalidDimensions(_))));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn unsupported_extension() {
+        assert!(from_extension("png").is_none());
+    }
+
+    fn write_bytes(extension: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        from_extension(extension)
+            .unwrap()
+            .write(&Lut3dLinear::identity(), &mut bytes)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn detect_cube_wrong_extension() {
+        let bytes = write_bytes("cube");
+        let format = detect(Some("txt"), &bytes).unwrap();
+        assert_eq!(&["cube"], format.extensions());
+        assert_eq!(
+            Lut3dLinear::identity().to_rgba(),
+            format.read(&mut Cursor::new(bytes)).unwrap().to_rgba()
+        );
+    }
+
+    #[test]
+    fn detect_nutexb_wrong_extension() {
+        let bytes = write_bytes("nutexb");
+        assert_eq!(
+            &["nutexb"],
+            detect(Some("cube"), &bytes).unwrap().extensions()
+        );
+        assert_eq!(&["nutexb"], detect(None, &bytes).unwrap().extensions());
+    }
+
+    #[test]
+    fn detect_extension_hint() {
+        // Use the extension if the contents don't match any format.
+        assert_eq!(&["cube"], detect(Some("cube"), b"").unwrap().extensions());
+        assert!(detect(Some("png"), b"").is_none());
+    }
+}
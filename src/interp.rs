@@ -51,6 +51,40 @@ pub fn trilinear(
     linear(z, z0, z1, face0, face1)
 }
 
+// https://doi.org/10.1117/12.149224
+pub fn tetrahedral(xyz: (f32, f32, f32), fxyz: [f32; 8]) -> f32 {
+    let (x, y, z) = xyz;
+
+    // Interpolate the 4 corners of the tetrahedron containing xyz in the unit cube.
+    // All tetrahedra share the corners at 0b000 and 0b111.
+    // Binary indices are fzyx in row-major order.
+    let f = |i: usize| fxyz[i];
+    if x > y {
+        if y > z {
+            f(0b000)
+                + x * (f(0b001) - f(0b000))
+                + y * (f(0b011) - f(0b001))
+                + z * (f(0b111) - f(0b011))
+        } else if x > z {
+            f(0b000)
+                + x * (f(0b001) - f(0b000))
+                + z * (f(0b101) - f(0b001))
+                + y * (f(0b111) - f(0b101))
+        } else {
+            f(0b000)
+                + z * (f(0b100) - f(0b000))
+                + x * (f(0b101) - f(0b100))
+                + y * (f(0b111) - f(0b101))
+        }
+    } else if z > y {
+        f(0b000) + z * (f(0b100) - f(0b000)) + y * (f(0b110) - f(0b100)) + x * (f(0b111) - f(0b110))
+    } else if z > x {
+        f(0b000) + y * (f(0b010) - f(0b000)) + z * (f(0b110) - f(0b010)) + x * (f(0b111) - f(0b110))
+    } else {
+        f(0b000) + y * (f(0b010) - f(0b000)) + x * (f(0b011) - f(0b010)) + z * (f(0b111) - f(0b011))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -109,4 +143,28 @@ mod tests {
             trilinear((0.5, 0.5, 0.5), 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, values)
         )
     }
+
+    #[test]
+    fn tetrahedral_interpolation() {
+        // Test corners.
+        let xyz = [
+            (0.0, 0.0, 0.0),
+            (1.0, 0.0, 0.0),
+            (0.0, 1.0, 0.0),
+            (1.0, 1.0, 0.0),
+            (0.0, 0.0, 1.0),
+            (1.0, 0.0, 1.0),
+            (0.0, 1.0, 1.0),
+            (1.0, 1.0, 1.0),
+        ];
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+
+        for i in 0..values.len() {
+            assert_eq!(values[i], tetrahedral(xyz[i], values));
+        }
+
+        // Test center.
+        // The main diagonal only uses the first and last corner.
+        assert_eq!(4.5, tetrahedral((0.5, 0.5, 0.5), values));
+    }
 }
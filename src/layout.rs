@@ -0,0 +1,128 @@
+use image::RgbaImage;
+
+use crate::LutError;
+
+// The number of slices in each row and column of the grid.
+fn grid_tiles(size: u32) -> u32 {
+    (size as f64).sqrt().ceil() as u32
+}
+
+/// Rearranges an image with slices in z arranged horizontally into a square grid of slices.
+/// Slices are placed left to right and then top to bottom.
+/// For example, a 256x16 image for a 16x16x16 LUT becomes a 64x64 image with 4 slices per row.
+/// Unused tiles in the grid are transparent black.
+pub fn strip_to_grid(img: &RgbaImage) -> Result<RgbaImage, LutError> {
+    let size = img.height();
+    if img.width() != size * size {
+        return Err(LutError::InvalidDimensions(
+            "Invalid dimensions. Expected width to equal height * height.",
+        ));
+    }
+
+    let tiles = grid_tiles(size);
+    let mut grid = RgbaImage::new(tiles * size, tiles * size);
+    for z in 0..size {
+        let (tile_x, tile_y) = ((z % tiles) * size, (z / tiles) * size);
+        for y in 0..size {
+            for x in 0..size {
+                let pixel = *img.get_pixel(z * size + x, y);
+                grid.put_pixel(tile_x + x, tile_y + y, pixel);
+            }
+        }
+    }
+
+    Ok(grid)
+}
+
+/// Rearranges an image created by [strip_to_grid] back into a horizontal strip of slices.
+/// The LUT size is inferred from the image dimensions.
+pub fn grid_to_strip(img: &RgbaImage) -> Result<RgbaImage, LutError> {
+    if img.width() != img.height() {
+        return Err(LutError::InvalidDimensions(
+            "Invalid dimensions. Expected width to equal height.",
+        ));
+    }
+
+    let size = (1..=img.width())
+        .find(|size| grid_tiles(*size) * size == img.width())
+        .ok_or(LutError::InvalidDimensions(
+            "Invalid dimensions. Expected a square grid of square slices.",
+        ))?;
+
+    let tiles = grid_tiles(size);
+    let mut strip = RgbaImage::new(size * size, size);
+    for z in 0..size {
+        let (tile_x, tile_y) = ((z % tiles) * size, (z / tiles) * size);
+        for y in 0..size {
+            for x in 0..size {
+                let pixel = *img.get_pixel(tile_x + x, tile_y + y);
+                strip.put_pixel(z * size + x, y, pixel);
+            }
+        }
+    }
+
+    Ok(strip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_default_lut, create_identity_lut_f32, Lut3dLinear};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn strip_to_grid_size16() {
+        let strip = RgbaImage::from_raw(256, 16, create_default_lut()).unwrap();
+        let grid = strip_to_grid(&strip).unwrap();
+
+        assert_eq!(64, grid.width());
+        assert_eq!(64, grid.height());
+
+        // The second row starts with slice 4.
+        assert_eq!(strip.get_pixel(4 * 16 + 3, 5), grid.get_pixel(3, 16 + 5));
+        assert_eq!(strip, grid_to_strip(&grid).unwrap());
+    }
+
+    #[test]
+    fn strip_to_grid_size8() {
+        // 8 slices don't fill a 3x3 grid.
+        let lut = Lut3dLinear {
+            size: 8,
+            data: create_identity_lut_f32(8),
+        };
+        let strip = RgbaImage::try_from(&lut).unwrap();
+        let grid = strip_to_grid(&strip).unwrap();
+
+        assert_eq!(24, grid.width());
+        assert_eq!(24, grid.height());
+        assert_eq!(&image::Rgba([0u8; 4]), grid.get_pixel(23, 23));
+        assert_eq!(strip, grid_to_strip(&grid).unwrap());
+    }
+
+    #[test]
+    fn strip_to_grid_invalid_dimensions() {
+        let img = RgbaImage::new(128, 32);
+        assert!(matches!(
+            strip_to_grid(&img),
+            Err(LutError::InvalidDimensions(
+                "Invalid dimensions. Expected width to equal height * height."
+            ))
+        ));
+    }
+
+    #[test]
+    fn grid_to_strip_invalid_dimensions() {
+        assert!(matches!(
+            grid_to_strip(&RgbaImage::new(64, 32)),
+            Err(LutError::InvalidDimensions(
+                "Invalid dimensions. Expected width to equal height."
+            ))
+        ));
+        assert!(matches!(
+            grid_to_strip(&RgbaImage::new(7, 7)),
+            Err(LutError::InvalidDimensions(
+                "Invalid dimensions. Expected a square grid of square slices."
+            ))
+        ));
+    }
+}
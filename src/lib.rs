@@ -1,54 +1,275 @@
+use error::nutexb_error;
 use image::RgbaImage;
-use nutexb::NutexbFile;
+use lut3d::QuantizedLut;
+use nutexb::{NutexbFile, NutexbFormat};
 use std::convert::TryFrom;
 use std::error::Error;
 use std::fs::File;
 use std::path::Path;
 
-pub use cube::CubeLut3d;
-pub use lut3d::Lut3dLinear;
+#[cfg(feature = "zip")]
+pub use archive::read_zip_entry;
+pub use cube::{CubeColorSpace, CubeLut1d, CubeLut3d, CubeWriteOptions};
+pub use diff::{delta_e, difference, DeltaE, LutDifference};
+pub use embed::{read_png_source, write_png_with_source};
+pub use error::LutError;
+pub use layout::{grid_to_strip, strip_to_grid};
+pub use lut3d::{AxisOrder, ColorEncoding, Interpolation, Lut3dLinear, RoundingMode};
+pub use lut3d_rgb::Lut3dRgb;
+#[cfg(feature = "openexr")]
+pub use openexr::{read_exr_lut, write_exr_lut};
+pub use pattern::TestPattern;
+pub use sequence::{
+    read_sequence, read_sequence_dir, read_sequence_files, sample_sequence, write_sequence,
+};
 
+#[cfg(feature = "zip")]
+mod archive;
+#[cfg(feature = "ndarray")]
+mod array;
 mod color_correction;
 mod cube;
+mod diff;
+mod embed;
+mod error;
+pub mod format;
 mod interp;
+mod layout;
 mod lut3d;
+mod lut3d_rgb;
+#[cfg(feature = "openexr")]
+mod openexr;
+mod pattern;
+mod sequence;
 
-pub use color_correction::correct_lut;
+pub use color_correction::{
+    apply_stage, correct_lut, correct_lut_cancellable, correct_lut_with_params, correction_glsl,
+    uncorrect_lut, uncorrect_lut_with_params, StageParams,
+};
+
+/// The error returned when a long running operation is cancelled before completing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The operation was cancelled.")
+    }
+}
+
+impl Error for Cancelled {}
 
 /// Convert an image with dimensions ((size * size), size) to a Nutexb LUT.
-pub fn write_img_to_nutexb<P: AsRef<Path>>(
-    img: &RgbaImage,
-    path: &P,
-) -> Result<(), Box<dyn std::error::Error>> {
+pub fn write_img_to_nutexb<P: AsRef<Path>>(img: &RgbaImage, path: &P) -> Result<(), LutError> {
     let linear = Lut3dLinear::try_from(img)?;
     write_lut_to_nutexb(&linear, path)
 }
 
 /// Convert a `Lut3dLinear` lut to Nutexb.
-pub fn write_lut_to_nutexb<P: AsRef<Path>>(
+pub fn write_lut_to_nutexb<P: AsRef<Path>>(lut: &Lut3dLinear, path: P) -> Result<(), LutError> {
+    write_lut_to_nutexb_rounded(lut, RoundingMode::Nearest, path)
+}
+
+/// Convert a `Lut3dLinear` lut to Nutexb using `rounding` to quantize the values to 8 bits.
+pub fn write_lut_to_nutexb_rounded<P: AsRef<Path>>(
+    lut: &Lut3dLinear,
+    rounding: RoundingMode,
+    path: P,
+) -> Result<(), LutError> {
+    write_lut_to_nutexb_encoded(lut, rounding, ColorEncoding::Unorm, path)
+}
+
+/// Convert a `Lut3dLinear` lut to Nutexb like [write_lut_to_nutexb_rounded]
+/// using `encoding` to select between the Unorm and Srgb texture formats.
+pub fn write_lut_to_nutexb_encoded<P: AsRef<Path>>(
     lut: &Lut3dLinear,
+    rounding: RoundingMode,
+    encoding: ColorEncoding,
     path: P,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // TODO: This only works for size 16?
-    NutexbFile::create(lut, "color_grading_lut")?.write_to_file(path)
+) -> Result<(), LutError> {
+    validate_nutexb_lut(lut)?;
+    let quantized = QuantizedLut {
+        lut,
+        rounding,
+        encoding,
+    };
+    NutexbFile::create(&quantized, "color_grading_lut")
+        .and_then(|nutexb| nutexb.write_to_file(path))
+        .map_err(nutexb_error)
+}
+
+/// Replaces the image data in the nutexb at `existing_path` with `lut` and writes the result to `out_path`.
+/// All other nutexb fields like the internal name are preserved from the original file.
+/// This fails if the existing nutexb doesn't have the same dimensions and format as a nutexb created from `lut`.
+pub fn replace_nutexb_data<P: AsRef<Path>, Q: AsRef<Path>>(
+    existing_path: P,
+    lut: &Lut3dLinear,
+    out_path: Q,
+) -> Result<(), LutError> {
+    let mut nutexb = NutexbFile::read_from_file(existing_path).map_err(nutexb_error)?;
+
+    // Keep the original Unorm or Srgb format.
+    let encoding = match nutexb.footer.image_format {
+        NutexbFormat::R8G8B8A8Srgb => ColorEncoding::Srgb,
+        _ => ColorEncoding::Unorm,
+    };
+    validate_nutexb_lut(lut)?;
+    let quantized = QuantizedLut {
+        lut,
+        rounding: RoundingMode::Nearest,
+        encoding,
+    };
+    let new =
+        NutexbFile::create(&quantized, nutexb.footer.string.to_string()).map_err(nutexb_error)?;
+
+    let footer = &nutexb.footer;
+    if (footer.width, footer.height, footer.depth)
+        != (new.footer.width, new.footer.height, new.footer.depth)
+        || footer.image_format != new.footer.image_format
+        || footer.mipmap_count != new.footer.mipmap_count
+        || footer.layer_count != new.footer.layer_count
+        || nutexb.data.len() != new.data.len()
+    {
+        return Err(LutError::InvalidDimensions(
+            "The existing nutexb dimensions or format do not match the LUT.",
+        ));
+    }
+
+    nutexb.data = new.data;
+    nutexb.write_to_file(out_path).map_err(nutexb_error)
+}
+
+/// Checks that `lut` can be swizzled correctly for a nutexb before passing the data to the swizzling code.
+pub(crate) fn validate_nutexb_lut(lut: &Lut3dLinear) -> Result<(), LutError> {
+    validate_nutexb_size(lut.size)?;
+    validate_data_length(lut)
+}
+
+/// Checks that `lut` has exactly one RGBA value for each node.
+pub(crate) fn validate_data_length(lut: &Lut3dLinear) -> Result<(), LutError> {
+    // The public fields allow data that doesn't match the size.
+    let expected = lut.size * lut.size * lut.size * 4;
+    if lut.data.len() != expected {
+        return Err(LutError::DataLengthMismatch {
+            size: lut.size,
+            expected,
+            actual: lut.data.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks that a LUT with the given `size` can be swizzled correctly for a nutexb.
+/// The swizzling only supports power of two sizes up to 16 like the in game 16x16x16 LUT.
+pub(crate) fn validate_nutexb_size(size: usize) -> Result<(), LutError> {
+    if size.is_power_of_two() && size <= 16 {
+        Ok(())
+    } else {
+        Err(LutError::InvalidDimensions(
+            "Nutexb LUTs must have a power of two size of at most 16. Resize the LUT to 16 first.",
+        ))
+    }
 }
 
 /// Attempts to read the color grading LUT data from the given path.
 /// The final LUT will not be valid if `nutexb` does contain a 16x16x16 RGBA 3D LUT texture.  
-/// The conversion will fail if `nutexb` does not contain at least 16384 bytes of data.
-pub fn read_nutexb_lut<P: AsRef<Path>>(path: P) -> Result<Lut3dLinear, Box<dyn Error>> {
+/// RGB values for `R8G8B8A8Srgb` textures are converted to linear like the GPU does when sampling.  
+/// The conversion will fail if `nutexb` does not contain at least `depth * depth * depth * 4` bytes of data.
+pub fn read_nutexb_lut<P: AsRef<Path>>(path: P) -> Result<Lut3dLinear, LutError> {
+    let nutexb = NutexbFile::read_from_file(path).map_err(nutexb_error)?;
+    lut_from_nutexb(&nutexb)
+}
+
+fn lut_from_nutexb(nutexb: &NutexbFile) -> Result<Lut3dLinear, LutError> {
     // TODO: Error if dimensions aren't supported?
-    let nutexb = NutexbFile::read_from_file(path)?;
-    Ok(Lut3dLinear::from_rgba(
-        nutexb.footer.depth as usize,
-        nutexb.deswizzled_data()?,
-    ))
+    let format = nutexb.footer.image_format;
+    if !matches!(
+        format,
+        NutexbFormat::R8G8B8A8Unorm | NutexbFormat::R8G8B8A8Srgb
+    ) {
+        return Err(LutError::UnsupportedFormat(format!("{:?}", format)));
+    }
+
+    let size = nutexb.footer.depth as usize;
+    let data = nutexb.deswizzled_data().map_err(nutexb_error)?;
+
+    // Sampling assumes the data has a value for every RGBA texel.
+    let expected = size * size * size * 4;
+    if data.len() < expected {
+        return Err(LutError::NotEnoughData {
+            size,
+            expected,
+            actual: data.len(),
+        });
+    }
+
+    let mut lut = Lut3dLinear::from_rgba(size, data);
+    if format == NutexbFormat::R8G8B8A8Srgb {
+        for rgba in lut.data.chunks_exact_mut(4) {
+            for value in &mut rgba[..3] {
+                *value = color_correction::linear(*value);
+            }
+        }
+    }
+    Ok(lut)
+}
+
+/// Checks if deswizzling and then swizzling the data in the nutexb at `path` produces the original data.
+/// Textures that don't round trip were likely created with an incorrect swizzle or dimensions.
+pub fn nutexb_swizzle_round_trips<P: AsRef<Path>>(path: P) -> Result<bool, LutError> {
+    let nutexb = NutexbFile::read_from_file(path).map_err(nutexb_error)?;
+    swizzle_round_trips(&nutexb)
+}
+
+fn swizzle_round_trips(nutexb: &NutexbFile) -> Result<bool, LutError> {
+    let data = nutexb.deswizzled_data().map_err(nutexb_error)?;
+    if validate_nutexb_size(nutexb.footer.depth as usize).is_err() {
+        return Ok(false);
+    }
+
+    // Use the raw bytes to avoid any color space conversions.
+    let lut = Lut3dLinear::from_rgba(nutexb.footer.depth as usize, data);
+    let reswizzled = NutexbFile::create(&lut, nutexb.footer.string.to_string());
+    Ok(matches!(reswizzled, Ok(new) if new.data == nutexb.data))
+}
+
+/// Returns the size of a LUT with `len` bytes of unswizzled 8-bit RGBA data like a raw .bin file.
+/// Returns `None` if `len` isn't `size * size * size * 4` for some nonzero size.
+pub fn infer_size_from_len(len: usize) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+
+    // Check the neighboring sizes in case the cube root is slightly off.
+    let size = ((len / 4) as f64).cbrt().round() as usize;
+    (size.saturating_sub(1)..=size + 1).find(|s| s * s * s * 4 == len)
 }
 
 fn index3d(x: usize, y: usize, z: usize, width: usize, height: usize) -> usize {
     z * width * height + y * width + x
 }
 
+// The coordinate in the range 0.0 to 1.0 for the node at index along an axis with size nodes.
+// A LUT with a single node uses 0.0 to avoid dividing by zero.
+fn normalized_coord(index: usize, size: usize) -> f32 {
+    index as f32 / size.saturating_sub(1).max(1) as f32
+}
+
+// The start node of the region containing coord along an axis with size nodes
+// and the position relative to that node in units of nodes.
+// The start is clamped to size - 2 so that coordinates of exactly 1.0 use the last region with a fraction of 1.0.
+// Coordinates outside 0.0 to 1.0 have fractions outside 0.0 to 1.0 to extrapolate from the edge regions.
+fn coord_to_node(coord: f32, size: usize) -> (usize, f32) {
+    let position = coord * size.saturating_sub(1) as f32;
+    let node = (position as usize).min(size.saturating_sub(2));
+    (node, position - node as f32)
+}
+
+// The xyz coordinates in the range 0.0 to 1.0 for a node in a LUT with the given size.
+fn node_coord(x: usize, y: usize, z: usize, size: usize) -> [f32; 3] {
+    [x, y, z].map(|i| normalized_coord(i, size))
+}
+
 fn create_identity_lut_f32(size: usize) -> Vec<f32> {
     let channels = 4;
 
@@ -57,10 +278,8 @@ fn create_identity_lut_f32(size: usize) -> Vec<f32> {
         for y in 0..size {
             for x in 0..size {
                 let offset = index3d(x, y, z, size, size) * channels;
-                result[offset] = x as f32 / (size - 1) as f32;
-                result[offset + 1] = y as f32 / (size - 1) as f32;
-                result[offset + 2] = z as f32 / (size - 1) as f32;
-                result[offset + 3] = 1.0;
+                let [r, g, b] = node_coord(x, y, z, size);
+                result[offset..offset + 4].copy_from_slice(&[r, g, b, 1.0]);
             }
         }
     }
@@ -108,9 +327,343 @@ pub fn create_default_lut_f32() -> Vec<f32> {
 pub fn linear_lut_to_cube<P: AsRef<Path>>(
     lut_linear: &Lut3dLinear,
     output: P,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), LutError> {
     let cube = CubeLut3d::from(lut_linear);
     let mut file = File::create(output)?;
     cube.write(&mut file)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique_rgba(size: usize) -> Vec<u8> {
+        // Encode the node index in RGB so every node has a different color.
+        let mut data = Vec::new();
+        for z in 0..size {
+            for y in 0..size {
+                for x in 0..size {
+                    let i = index3d(x, y, z, size, size);
+                    data.extend_from_slice(&[i as u8, (i >> 8) as u8, (x * 16 + y) as u8, z as u8]);
+                }
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn normalized_coord_size16() {
+        assert_eq!(0.0, normalized_coord(0, 16));
+        assert_eq!(1.0 / 15.0, normalized_coord(1, 16));
+        assert_eq!(8.0 / 15.0, normalized_coord(8, 16));
+        assert_eq!(1.0, normalized_coord(15, 16));
+        assert_eq!([1.0 / 15.0, 0.0, 1.0], node_coord(1, 0, 15, 16));
+    }
+
+    #[test]
+    fn coord_to_node_edges() {
+        assert_eq!((0, 0.0), coord_to_node(0.0, 16));
+        assert_eq!((14, 1.0), coord_to_node(1.0, 16));
+    }
+
+    #[test]
+    fn coord_to_node_interior() {
+        let (node, fraction) = coord_to_node(0.5, 16);
+        assert_eq!(7, node);
+        approx::assert_relative_eq!(0.5, fraction);
+
+        // Exact nodes have no fractional part.
+        assert_eq!((3, 0.0), coord_to_node(3.0 / 15.0, 16));
+    }
+
+    #[test]
+    fn coord_to_node_outside() {
+        assert_eq!((0, -1.5), coord_to_node(-0.1, 16));
+        let (node, fraction) = coord_to_node(1.2, 16);
+        assert_eq!(14, node);
+        approx::assert_relative_eq!(4.0, fraction, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn coord_to_node_small_sizes() {
+        assert_eq!((0, 0.0), coord_to_node(0.5, 1));
+        assert_eq!((0, 0.5), coord_to_node(0.5, 2));
+        assert_eq!((0, 1.0), coord_to_node(1.0, 2));
+    }
+
+    #[test]
+    fn normalized_coord_size1() {
+        assert_eq!(0.0, normalized_coord(0, 1));
+    }
+
+    #[test]
+    fn infer_size_from_len_cubes() {
+        assert_eq!(Some(16), infer_size_from_len(16384));
+        assert_eq!(Some(8), infer_size_from_len(2048));
+        assert_eq!(Some(1), infer_size_from_len(4));
+        assert_eq!(Some(65), infer_size_from_len(65 * 65 * 65 * 4));
+    }
+
+    #[test]
+    fn infer_size_from_len_invalid() {
+        assert_eq!(None, infer_size_from_len(0));
+        assert_eq!(None, infer_size_from_len(16383));
+        assert_eq!(None, infer_size_from_len(16 * 16 * 4));
+    }
+
+    #[test]
+    fn create_nutexb_unsupported_size() {
+        let lut = Lut3dLinear::default_stage().resample(33);
+        assert!(NutexbFile::create(&lut, "color_grading_lut").is_err());
+
+        let quantized = QuantizedLut {
+            lut: &lut,
+            rounding: RoundingMode::Nearest,
+            encoding: ColorEncoding::Srgb,
+        };
+        assert!(NutexbFile::create(&quantized, "color_grading_lut").is_err());
+    }
+
+    #[test]
+    fn swizzle_deswizzle_unique_values() {
+        let data = unique_rgba(16);
+        let lut = Lut3dLinear::from_rgba(16, data.clone());
+
+        let nutexb = NutexbFile::create(&lut, "color_grading_lut").unwrap();
+        assert_ne!(data, nutexb.data);
+        assert_eq!(data, nutexb.deswizzled_data().unwrap());
+    }
+
+    #[test]
+    fn deswizzle_swizzle_unique_values() {
+        let lut = Lut3dLinear::from_rgba(16, unique_rgba(16));
+        let nutexb = NutexbFile::create(&lut, "color_grading_lut").unwrap();
+
+        let deswizzled = Lut3dLinear::from_rgba(16, nutexb.deswizzled_data().unwrap());
+        let reswizzled = NutexbFile::create(&deswizzled, "color_grading_lut").unwrap();
+        assert_eq!(nutexb.data, reswizzled.data);
+    }
+
+    #[test]
+    fn swizzle_round_trips_created_nutexb() {
+        let lut = Lut3dLinear::from_rgba(16, unique_rgba(16));
+        let nutexb = NutexbFile::create(&lut, "color_grading_lut").unwrap();
+        assert!(swizzle_round_trips(&nutexb).unwrap());
+    }
+
+    #[test]
+    fn swizzle_round_trips_srgb_nutexb() {
+        let lut = Lut3dLinear::from_rgba(16, unique_rgba(16));
+        let quantized = QuantizedLut {
+            lut: &lut,
+            rounding: RoundingMode::Nearest,
+            encoding: ColorEncoding::Srgb,
+        };
+        let nutexb = NutexbFile::create(&quantized, "color_grading_lut").unwrap();
+        assert!(swizzle_round_trips(&nutexb).unwrap());
+    }
+
+    #[test]
+    fn swizzle_round_trips_wrong_dimensions() {
+        let lut = Lut3dLinear::from_rgba(16, unique_rgba(16));
+        let mut nutexb = NutexbFile::create(&lut, "color_grading_lut").unwrap();
+        nutexb.footer.width = 8;
+        assert!(!swizzle_round_trips(&nutexb).unwrap());
+    }
+
+    #[test]
+    fn read_truncated_nutexb_data() {
+        let lut = Lut3dLinear::from_rgba(16, unique_rgba(16));
+        let mut nutexb = NutexbFile::create(&lut, "color_grading_lut").unwrap();
+        nutexb.data.truncate(nutexb.data.len() / 2);
+
+        assert!(matches!(
+            lut_from_nutexb(&nutexb),
+            Err(LutError::Nutexb(_)) | Err(LutError::NotEnoughData { .. })
+        ));
+    }
+
+    #[test]
+    fn read_truncated_nutexb_dimensions() {
+        // The deswizzled data only covers half of each row.
+        let lut = Lut3dLinear::from_rgba(16, unique_rgba(16));
+        let mut nutexb = NutexbFile::create(&lut, "color_grading_lut").unwrap();
+        nutexb.footer.width = 8;
+
+        assert!(matches!(
+            lut_from_nutexb(&nutexb),
+            Err(LutError::NotEnoughData {
+                size: 16,
+                expected: 16384,
+                actual: 8192
+            })
+        ));
+    }
+
+    #[test]
+    fn read_unsupported_nutexb_format() {
+        let lut = Lut3dLinear::from_rgba(16, unique_rgba(16));
+        let mut nutexb = NutexbFile::create(&lut, "color_grading_lut").unwrap();
+        nutexb.footer.image_format = NutexbFormat::BC7Unorm;
+
+        assert!(matches!(
+            lut_from_nutexb(&nutexb),
+            Err(LutError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn replace_nutexb_data_preserves_footer() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("existing.nutexb");
+        let output = dir.path().join("output.nutexb");
+
+        let mut nutexb = NutexbFile::create(&Lut3dLinear::identity(), "stage_lut").unwrap();
+        nutexb.footer.unk2 = 7;
+        nutexb.write_to_file(&existing).unwrap();
+
+        let lut = Lut3dLinear::from_rgba(16, unique_rgba(16));
+        replace_nutexb_data(&existing, &lut, &output).unwrap();
+
+        let new = NutexbFile::read_from_file(&output).unwrap();
+        assert_eq!(nutexb.footer, new.footer);
+        assert_eq!(unique_rgba(16), new.deswizzled_data().unwrap());
+    }
+
+    #[test]
+    fn replace_nutexb_data_different_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("existing.nutexb");
+        let output = dir.path().join("output.nutexb");
+
+        write_lut_to_nutexb(&Lut3dLinear::identity(), &existing).unwrap();
+
+        let lut = Lut3dLinear::from_rgba(8, unique_rgba(8));
+        assert!(matches!(
+            replace_nutexb_data(&existing, &lut, &output),
+            Err(LutError::InvalidDimensions(_))
+        ));
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn default_stage_nutexb_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("color_grading_lut.nutexb");
+
+        let lut = Lut3dLinear::default_stage();
+        write_lut_to_nutexb(&lut, &path).unwrap();
+
+        // Quantizing to 8 bits should change each value by less than 1 LSB.
+        let new_lut = read_nutexb_lut(&path).unwrap();
+        assert_eq!(lut.size, new_lut.size);
+        approx::assert_relative_eq!(lut.data[..], new_lut.data[..], epsilon = 1.0 / 255.0);
+        assert_eq!(lut.to_rgba(), new_lut.to_rgba());
+    }
+
+    #[test]
+    fn nutexb_size8_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lut.nutexb");
+
+        let lut = Lut3dLinear::from_rgba(8, unique_rgba(8));
+        write_lut_to_nutexb(&lut, &path).unwrap();
+
+        let nutexb = NutexbFile::read_from_file(&path).unwrap();
+        assert_eq!(
+            (8, 8, 8),
+            (
+                nutexb.footer.width,
+                nutexb.footer.height,
+                nutexb.footer.depth
+            )
+        );
+        assert_eq!(lut, read_nutexb_lut(&path).unwrap());
+    }
+
+    #[test]
+    fn nutexb_data_length_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lut.nutexb");
+
+        for len in [100, 16 * 16 * 16 * 4 + 4] {
+            let lut = Lut3dLinear {
+                size: 16,
+                data: vec![0.0; len],
+            };
+            let error = write_lut_to_nutexb(&lut, &path).unwrap_err();
+            assert_eq!(
+                format!(
+                    "Expected 16384 values for a 16x16x16 RGBA LUT but found {}.",
+                    len
+                ),
+                error.to_string()
+            );
+            assert!(!path.exists());
+        }
+    }
+
+    #[test]
+    fn nutexb_unsupported_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lut.nutexb");
+
+        for size in [17, 32] {
+            let lut = Lut3dLinear::from_fn(size, |c| c);
+            assert!(matches!(
+                write_lut_to_nutexb(&lut, &path),
+                Err(LutError::InvalidDimensions(_))
+            ));
+            assert!(!path.exists());
+
+            // Resampling makes the LUT valid for nutexb.
+            write_lut_to_nutexb(&lut.resample(16), &path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    fn gradient_lut() -> Lut3dLinear {
+        Lut3dLinear::from_matrix(
+            16,
+            [[0.8, 0.15, 0.03], [0.1, 0.75, 0.1], [0.05, 0.05, 0.85]],
+            [0.011, 0.007, 0.003],
+        )
+    }
+
+    #[test]
+    fn nutexb_unorm_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lut.nutexb");
+
+        let lut = gradient_lut();
+        write_lut_to_nutexb_encoded(&lut, RoundingMode::Nearest, ColorEncoding::Unorm, &path)
+            .unwrap();
+
+        let nutexb = NutexbFile::read_from_file(&path).unwrap();
+        assert_eq!(NutexbFormat::R8G8B8A8Unorm, nutexb.footer.image_format);
+        assert_eq!(lut.to_rgba(), read_nutexb_lut(&path).unwrap().to_rgba());
+    }
+
+    #[test]
+    fn nutexb_srgb_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lut.nutexb");
+
+        let lut = gradient_lut();
+        write_lut_to_nutexb_encoded(&lut, RoundingMode::Nearest, ColorEncoding::Srgb, &path)
+            .unwrap();
+
+        let nutexb = NutexbFile::read_from_file(&path).unwrap();
+        assert_eq!(NutexbFormat::R8G8B8A8Srgb, nutexb.footer.image_format);
+
+        // The sRGB encoding is less precise for bright linear values.
+        let new_lut = read_nutexb_lut(&path).unwrap();
+        approx::assert_relative_eq!(lut.data[..], new_lut.data[..], epsilon = 0.005);
+
+        // The stored bytes should be sRGB encoded.
+        let data = nutexb.deswizzled_data().unwrap();
+        assert_eq!(255, data[3]);
+        assert!(data[4] > lut.to_rgba()[4]);
+    }
+}
@@ -1,14 +1,176 @@
 use std::convert::{TryFrom, TryInto};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
-use image::RgbaImage;
+use image::{imageops::FilterType, ColorType, DynamicImage, ImageBuffer, Rgba, RgbaImage};
 use nutexb::{NutexbFormat, ToNutexb};
 
+use crate::color_correction::{linear, srgb};
 use crate::{
-    create_default_lut_f32, create_identity_lut_f32, index3d, interp::trilinear, CubeLut3d,
+    coord_to_node, create_default_lut_f32, index3d,
+    interp::{bilinear, tetrahedral, trilinear},
+    node_coord, validate_nutexb_lut, Cancelled, CubeColorSpace, CubeLut3d, LutError,
 };
 
+/// Determines how floating point values are quantized to 8 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoundingMode {
+    /// Round to the nearest representable value.
+    #[default]
+    Nearest,
+    /// Round down to the nearest representable value.
+    Floor,
+    /// Round up with a probability equal to the fractional part.
+    /// The noise is a deterministic function of the value's index,
+    /// so the same LUT always produces the same output.
+    Stochastic,
+}
+
+/// Determines how the 8-bit values in a nutexb are decoded when the texture is sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorEncoding {
+    /// Values are stored directly like [NutexbFormat::R8G8B8A8Unorm].
+    #[default]
+    Unorm,
+    /// RGB values are sRGB encoded and converted to linear when sampled like [NutexbFormat::R8G8B8A8Srgb].
+    /// Alpha is stored directly.
+    Srgb,
+}
+
+impl ColorEncoding {
+    pub(crate) fn format(self) -> NutexbFormat {
+        match self {
+            ColorEncoding::Unorm => NutexbFormat::R8G8B8A8Unorm,
+            ColorEncoding::Srgb => NutexbFormat::R8G8B8A8Srgb,
+        }
+    }
+}
+
+impl RoundingMode {
+    fn quantize(self, value: f32, index: usize) -> u8 {
+        let value = value * 255.0;
+        // Casting to u8 saturates any values outside the range 0 to 255.
+        match self {
+            RoundingMode::Nearest => value.round() as u8,
+            RoundingMode::Floor => value.floor() as u8,
+            RoundingMode::Stochastic => {
+                let floor = value.floor();
+                if value - floor > noise(index) {
+                    (floor + 1.0) as u8
+                } else {
+                    floor as u8
+                }
+            }
+        }
+    }
+}
+
+// Hue is in degrees with saturation and value in the range 0.0 to 1.0.
+fn rgb_to_hsv([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    [hue, saturation, max]
+}
+
+fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [f32; 3] {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let m = v - c;
+    let [r, g, b] = match (h / 60.0) as u32 % 6 {
+        0 => [c, x, 0.0],
+        1 => [x, c, 0.0],
+        2 => [0.0, c, x],
+        3 => [0.0, x, c],
+        4 => [x, 0.0, c],
+        _ => [c, 0.0, x],
+    };
+    [r + m, g + m, b + m]
+}
+
+// Solve the linear system m * x = b using Cramer's rule.
+fn solve3(m: [[f32; 3]; 3], b: [f32; 3]) -> Option<[f32; 3]> {
+    let det = |m: [[f32; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+
+    let d = det(m);
+    if d.abs() < 1e-12 {
+        return None;
+    }
+
+    let mut x = [0.0; 3];
+    for (i, value) in x.iter_mut().enumerate() {
+        // Replace column i with b.
+        let mut mi = m;
+        for (row, b) in mi.iter_mut().zip(b) {
+            row[i] = b;
+        }
+        *value = det(mi) / d;
+    }
+    Some(x)
+}
+
+// Hash the index to a value in the range 0.0 to 1.0.
+// https://en.wikipedia.org/wiki/Xorshift
+fn noise(index: usize) -> f32 {
+    let mut x = (index as u32).wrapping_add(0x9E3779B9);
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    x as f32 / u32::MAX as f32
+}
+
+const INVERT_STEPS: usize = 20;
+
+// A 32x32 PNG with hue and saturation ramps above a neutral ramp.
+const PREVIEW_REFERENCE: &[u8] = include_bytes!("preview_reference.png");
+
+// Smaller images aren't worth the overhead of multiple threads.
+#[cfg(feature = "parallel")]
+const PARALLEL_PIXEL_COUNT: u32 = 256 * 256;
+
+/// The method for calculating values between LUT nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    /// Use the value of the closest node.
+    Nearest,
+    /// Interpolate the 8 nodes of the surrounding cube.
+    #[default]
+    Trilinear,
+    /// Interpolate the 4 nodes of the surrounding tetrahedron.
+    Tetrahedral,
+}
+
+/// The order of the data points in a LUT file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AxisOrder {
+    /// Red changes fastest and blue changes slowest like in game nutexb LUTs and the .cube spec.
+    #[default]
+    RedFastest,
+    /// Blue changes fastest and red changes slowest.
+    BlueFastest,
+}
+
 /// A 3D RGBA LUT with unswizzled data in row major order.
 /// Values are written to data using a nested ZYX loops with X being the innermost loop.
+///
+/// Sampling, resampling, and composing support any size like the common .cube sizes of 17, 25, or 33.
+/// Writing a nutexb only supports power of two sizes up to 16,
+/// so other sizes need to be resampled with [Lut3dLinear::resample] first.
 // TODO: It makes sense to just use float here instead.
 #[derive(Debug, PartialEq)]
 pub struct Lut3dLinear {
@@ -32,264 +194,1395 @@ impl Lut3dLinear {
         }
     }
 
-    pub fn to_rgba(&self) -> Vec<u8> {
-        self.data.iter().map(|f| (f * 255.0) as u8).collect()
+    /// Creates a LUT from `size * size * size` RGB float values with alpha set to 1.0.
+    pub fn from_rgb_f32(size: usize, data: Vec<f32>) -> Result<Self, LutError> {
+        if data.len() != size * size * size * 3 {
+            return Err(LutError::InvalidDimensions(
+                "Invalid data length. Expected size * size * size RGB values.",
+            ));
+        }
+
+        Ok(Self {
+            size,
+            data: data
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 1.0])
+                .collect(),
+        })
     }
 
-    pub fn default_stage() -> Self {
-        Self {
-            size: 16,
-            data: create_default_lut_f32(),
+    /// Reads a LUT with the given `size` from the top left `(size * size, size)` region of `img`.
+    /// Unlike the [TryFrom] conversion, this allows images with padding around the LUT slices.
+    pub fn from_image_sized(img: &RgbaImage, size: usize) -> Result<Self, LutError> {
+        let (width, height) = ((size * size) as u32, size as u32);
+        if img.width() < width || img.height() < height {
+            return Err(LutError::InvalidDimensions(
+                "Invalid dimensions. Expected the image to contain at least size * size by size pixels.",
+            ));
         }
+
+        let region = image::imageops::crop_imm(img, 0, 0, width, height).to_image();
+        Ok(Self::from_rgba(size, region.into_raw()))
     }
 
-    pub fn identity() -> Self {
+    /// Reads a LUT like [Lut3dLinear::from_image_sized] from an image in any color type.
+    /// 16-bit and floating point images preserve their extra precision like [Lut3dLinear::from_dynamic_image].
+    pub fn from_dynamic_image_sized(img: &DynamicImage, size: usize) -> Result<Self, LutError> {
+        let (width, height) = ((size * size) as u32, size as u32);
+        if img.width() < width || img.height() < height {
+            return Err(LutError::InvalidDimensions(
+                "Invalid dimensions. Expected the image to contain at least size * size by size pixels.",
+            ));
+        }
+
+        Self::from_dynamic_image(&img.crop_imm(0, 0, width, height))
+    }
+
+    /// Creates a LUT from an image with the same layout as [RgbaImage] in any color type.
+    /// 16-bit and floating point images preserve their extra precision instead of converting to 8 bits.
+    /// Images without an alpha channel use an alpha of 1.0.
+    pub fn from_dynamic_image(img: &DynamicImage) -> Result<Self, LutError> {
+        if img.width() != img.height() * img.height() {
+            return Err(LutError::InvalidDimensions(
+                "Invalid dimensions. Expected width to equal height * height.",
+            ));
+        }
+
+        let size = img.height() as usize;
+        match img.color() {
+            ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => {
+                Ok(Self::from_rgba16(size, img.to_rgba16().into_raw()))
+            }
+            ColorType::Rgb32F | ColorType::Rgba32F => Ok(Self {
+                size,
+                data: img.to_rgba32f().into_raw(),
+            }),
+            _ => Ok(Self::from_rgba(size, img.to_rgba8().into_raw())),
+        }
+    }
+
+    /// Creates a LUT from 16 bits per channel RGBA data like a 16-bit PNG.
+    pub fn from_rgba16(size: usize, data: Vec<u16>) -> Self {
         Self {
-            size: 16,
-            data: create_identity_lut_f32(16),
+            size,
+            data: data.into_iter().map(|u| u as f32 / 65535.0).collect(),
         }
     }
 
-    pub fn set_rgba(&mut self, x: usize, y: usize, z: usize, rgba: [f32; 4]) {
-        let i = index3d(x, y, z, self.size, self.size);
-        self.data[i * 4..i * 4 + 4].copy_from_slice(&rgba);
+    /// Quantizes the data to 16 bits per channel by rounding to the nearest value.
+    pub fn to_rgba16(&self) -> Vec<u16> {
+        self.data
+            .iter()
+            .map(|f| (f.clamp(0.0, 1.0) * 65535.0).round() as u16)
+            .collect()
     }
 
-    /// Samples a point in the LUT using 3D coordinates in the range `0.0` to `1.0`.
-    /// Coordinate values outside this range are preserved.
-    pub fn sample_rgba_trilinear(&self, x: f32, y: f32, z: f32) -> [f32; 4] {
-        let mut result = [0.0; 4];
+    /// Quantizes the data to 8 bits per channel using [RoundingMode::Nearest].
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.to_rgba_rounded(RoundingMode::Nearest)
+    }
 
-        // TODO: Does this work for an empty lut?
-        let max_index = (self.size - 1) as f32;
+    /// Quantizes the data to 8 bits per channel using the specified `rounding`.
+    pub fn to_rgba_rounded(&self, rounding: RoundingMode) -> Vec<u8> {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(i, f)| rounding.quantize(*f, i))
+            .collect()
+    }
 
-        // Find the endpoints of the 2x2 region containing the xyz coordinate.
-        let x0 = ((x * max_index) as usize).clamp(0, self.size - 1);
-        let x1 = ((x * max_index).ceil() as usize).clamp(0, self.size - 1);
+    /// Parses a hex dump of the 8-bit RGBA data like `"00000000 110000ff ..."`.
+    /// Whitespace and newlines between digits are ignored.
+    pub fn from_hex(size: usize, hex: &str) -> Result<Self, LutError> {
+        let digits: Vec<u8> = hex
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()
+            .ok_or_else(|| LutError::Parse("Invalid hex digit.".into()))?;
 
-        let y0 = ((y * max_index) as usize).clamp(0, self.size - 1);
-        let y1 = ((y * max_index).ceil() as usize).clamp(0, self.size - 1);
+        if digits.len() != size * size * size * 4 * 2 {
+            return Err(LutError::Parse(
+                "Hex data length does not agree with size.".into(),
+            ));
+        }
 
-        let z0 = ((z * max_index) as usize).clamp(0, self.size - 1);
-        let z1 = ((z * max_index).ceil() as usize).clamp(0, self.size - 1);
+        let data = digits.chunks_exact(2).map(|d| d[0] << 4 | d[1]).collect();
+        Ok(Self::from_rgba(size, data))
+    }
 
-        for (c, component) in result.iter_mut().enumerate() {
-            let f000 = self.data[index3d(x0, y0, z0, self.size, self.size) * 4 + c];
-            let f001 = self.data[index3d(x1, y0, z0, self.size, self.size) * 4 + c];
-            let f010 = self.data[index3d(x0, y1, z0, self.size, self.size) * 4 + c];
-            let f011 = self.data[index3d(x1, y1, z0, self.size, self.size) * 4 + c];
-            let f100 = self.data[index3d(x0, y0, z1, self.size, self.size) * 4 + c];
-            let f101 = self.data[index3d(x1, y0, z1, self.size, self.size) * 4 + c];
-            let f110 = self.data[index3d(x0, y1, z1, self.size, self.size) * 4 + c];
-            let f111 = self.data[index3d(x1, y1, z1, self.size, self.size) * 4 + c];
-
-            *component = trilinear(
-                (x, y, z),
-                0.0,
-                1.0,
-                0.0,
-                1.0,
-                0.0,
-                1.0,
-                [f000, f001, f010, f011, f100, f101, f110, f111],
-            );
+    /// Converts the 8-bit RGBA data to hex with one line for each row of nodes.
+    pub fn to_hex(&self) -> String {
+        let mut hex = String::new();
+        for row in self.to_rgba().chunks(self.size.max(1) * 4) {
+            let nodes: Vec<String> = row
+                .chunks(4)
+                .map(|rgba| rgba.iter().map(|b| format!("{b:02x}")).collect())
+                .collect();
+            hex += &nodes.join(" ");
+            hex.push('\n');
         }
+        hex
+    }
 
-        result
+    /// Hashes the size and 8-bit data from [Lut3dLinear::to_rgba] for caching converted outputs.
+    /// LUTs with float values that only differ by less than the 8-bit quantization hash to the same value.
+    /// The hash is stable across runs and platforms.
+    pub fn content_hash(&self) -> u64 {
+        // https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function
+        (self.size as u64)
+            .to_le_bytes()
+            .iter()
+            .chain(self.to_rgba().iter())
+            .fold(0xcbf29ce484222325, |hash, b| {
+                (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+            })
     }
-}
 
-impl From<CubeLut3d> for Lut3dLinear {
-    fn from(value: CubeLut3d) -> Self {
-        let mut data = Vec::new();
+    /// Converts the data in `cube` listed in the specified `order` to the ZYX order used by [Lut3dLinear].
+    /// Returns [LutError::DataLengthMismatch] if the number of data points doesn't match the size.
+    /// Nodes for a domain other than `0.0` to `1.0` are resampled to cover inputs from `0.0` to `1.0`.
+    pub fn from_cube(cube: &CubeLut3d, order: AxisOrder) -> Result<Self, LutError> {
+        // The public fields allow data that doesn't match the size.
+        let size = cube.size as usize;
+        let expected = size * size * size;
+        if cube.data.len() != expected {
+            return Err(LutError::DataLengthMismatch {
+                size,
+                expected: expected * 4,
+                actual: cube.data.len() * 4,
+            });
+        }
+
+        let mut data = vec![0.0; expected * 4];
+
+        for (i, (r, g, b)) in cube.data.iter().enumerate() {
+            let index = match order {
+                AxisOrder::RedFastest => i,
+                AxisOrder::BlueFastest => {
+                    // Swap the x and z coordinates.
+                    let (x, y, z) = (i / (size * size), (i / size) % size, i % size);
+                    index3d(x, y, z, size, size)
+                }
+            };
 
-        for (r, g, b) in value.data {
             // Always use 1.0 for alpha to match in game nutexb LUTs.
-            data.push(r);
-            data.push(g);
-            data.push(b);
-            data.push(1.0);
+            data[index * 4..index * 4 + 4].copy_from_slice(&[*r, *g, *b, 1.0]);
         }
 
-        Lut3dLinear {
-            size: value.size as usize,
-            data,
-        }
+        Ok(Self { size, data }.remap_domain(cube.domain_min, cube.domain_max))
     }
-}
 
-impl TryFrom<RgbaImage> for Lut3dLinear {
-    type Error = &'static str;
+    // Resample a LUT with nodes spanning the input domain from min to max
+    // so the nodes span the input range 0.0 to 1.0 used for sampling instead.
+    fn remap_domain(self, min: (f32, f32, f32), max: (f32, f32, f32)) -> Self {
+        if min == (0.0, 0.0, 0.0) && max == (1.0, 1.0, 1.0) {
+            return self;
+        }
 
-    /// Tries to convert an image with slices in z arranged horizontally along the top of the image.
-    /// For example, a 16x16x16 LUT image must have dimensions at least 256x16 pixels.
-    fn try_from(value: RgbaImage) -> Result<Self, Self::Error> {
-        (&value).try_into()
+        // Channels with an empty domain can't be remapped, so use the coordinates as is.
+        let (min, max) = ([min.0, min.1, min.2], [max.0, max.1, max.2]);
+        Self::from_fn(self.size, |rgb| {
+            let [x, y, z] = [0, 1, 2].map(|c| {
+                if max[c] > min[c] {
+                    (rgb[c] - min[c]) / (max[c] - min[c])
+                } else {
+                    rgb[c]
+                }
+            });
+            let [r, g, b, _] = self.sample_rgba_trilinear(x, y, z);
+            [r, g, b]
+        })
     }
-}
 
-impl TryFrom<&RgbaImage> for Lut3dLinear {
-    type Error = &'static str;
-
-    /// Tries to convert an image with slices in z arranged horizontally along the top of the image.
-    /// For example, a 16x16x16 LUT image must have dimensions at least 256x16 pixels.
-    fn try_from(value: &RgbaImage) -> Result<Self, Self::Error> {
-        if value.width() != value.height() * value.height() {
-            Err("Invalid dimensions. Expected width to equal height * height.")
-        } else {
-            Ok(Lut3dLinear::from_rgba(
-                value.height() as usize,
-                value.as_flat_samples().samples.to_vec(),
-            ))
+    pub fn default_stage() -> Self {
+        let lut = Self::default_stage_ref();
+        Self {
+            size: lut.size,
+            data: lut.data.clone(),
         }
     }
-}
 
-impl TryFrom<Lut3dLinear> for RgbaImage {
-    type Error = &'static str;
+    /// The same LUT as [Lut3dLinear::default_stage] but built only once and shared between calls.
+    /// Use this to avoid copying the data when the LUT is only read.
+    pub fn default_stage_ref() -> &'static Self {
+        static DEFAULT_STAGE: OnceLock<Lut3dLinear> = OnceLock::new();
+        DEFAULT_STAGE.get_or_init(|| Self {
+            size: 16,
+            data: create_default_lut_f32(),
+        })
+    }
 
-    fn try_from(value: Lut3dLinear) -> Result<Self, Self::Error> {
-        Self::try_from(&value)
+    pub fn identity() -> Self {
+        Self::from_fn(16, |rgb| rgb)
     }
-}
 
-impl TryFrom<&Lut3dLinear> for RgbaImage {
-    type Error = &'static str;
+    /// Creates a LUT of the given `size` by evaluating `f` for the RGB coordinates of each node.
+    /// The coordinates are in the range `0.0` to `1.0`, and alpha is always 1.0.
+    pub fn from_fn<F: Fn([f32; 3]) -> [f32; 3]>(size: usize, f: F) -> Self {
+        let mut data = Vec::with_capacity(size * size * size * 4);
+        for z in 0..size {
+            for y in 0..size {
+                for x in 0..size {
+                    let [r, g, b] = f(node_coord(x, y, z, size));
+                    data.extend_from_slice(&[r, g, b, 1.0]);
+                }
+            }
+        }
 
-    fn try_from(value: &Lut3dLinear) -> Result<Self, Self::Error> {
-        RgbaImage::from_raw(
-            (value.size * value.size) as u32,
-            value.size as u32,
-            value.to_rgba(),
-        )
-        .ok_or("Error creating RgbaImage.")
+        Self { size, data }
     }
-}
 
-impl ToNutexb for Lut3dLinear {
-    fn width(&self) -> u32 {
-        self.size as u32
+    /// Creates a LUT of the given `size` that applies `matrix` and then adds `offset` to each RGB color.
+    /// The identity matrix with an offset of zero produces an identity LUT.
+    pub fn from_matrix(size: usize, matrix: [[f32; 3]; 3], offset: [f32; 3]) -> Self {
+        Self::from_fn(size, |rgb| {
+            let mut result = [0.0; 3];
+            for (i, row) in matrix.iter().enumerate() {
+                result[i] = row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2] + offset[i];
+            }
+            result
+        })
     }
 
-    fn height(&self) -> u32 {
-        self.size as u32
+    /// Reorders the RGB channels so the output channel `i` uses the input channel `order[i]`.
+    /// For example, `[2, 1, 0]` swaps the red and blue channels.
+    pub fn swap_channels(&self, order: [usize; 3]) -> Self {
+        let mut data = self.data.clone();
+        for (new, old) in data.chunks_exact_mut(4).zip(self.data.chunks_exact(4)) {
+            for (c, i) in order.iter().enumerate() {
+                new[c] = old[*i];
+            }
+        }
+
+        Self {
+            size: self.size,
+            data,
+        }
     }
 
-    fn depth(&self) -> u32 {
-        self.size as u32
+    /// Clamps the RGB values to the range `0.0` to `1.0` supported by 8-bit formats like nutexb.
+    /// Alpha is unchanged.
+    pub fn clamp_to_unit(&mut self) {
+        for rgba in self.data.chunks_exact_mut(4) {
+            for c in &mut rgba[..3] {
+                *c = c.clamp(0.0, 1.0);
+            }
+        }
     }
 
-    fn image_data(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        Ok(self.to_rgba())
+    /// Rounds each value to the nearest multiple of `1.0 / 255.0` in the range `0.0` to `1.0`.
+    /// The data then exactly matches its 8-bit quantization from [Lut3dLinear::to_rgba],
+    /// which keeps comparisons and round trips through 8-bit formats like nutexb stable.
+    pub fn snap_to_byte_grid(&mut self) {
+        for (i, value) in self.data.iter_mut().enumerate() {
+            *value = RoundingMode::Nearest.quantize(*value, i) as f32 / 255.0;
+        }
     }
 
-    fn mipmap_count(&self) -> u32 {
-        1
+    /// Quantizes each RGB channel to `levels` evenly spaced values from `0.0` to `1.0` for a banded look.
+    /// Values of `levels` less than 2 are treated as 2.
+    pub fn posterize(&self, levels: usize) -> Self {
+        let steps = (levels.max(2) - 1) as f32;
+        let mut data = self.data.clone();
+        for rgba in data.chunks_exact_mut(4) {
+            for value in &mut rgba[..3] {
+                *value = (value.clamp(0.0, 1.0) * steps).round() / steps;
+            }
+        }
+
+        Self {
+            size: self.size,
+            data,
+        }
     }
 
-    fn layer_count(&self) -> u32 {
-        1
+    /// Rotates the hue of the RGB output for each node by `degrees` while preserving saturation and value.
+    /// Positive angles rotate red toward green and green toward blue.
+    pub fn rotate_hue(&self, degrees: f32) -> Self {
+        let mut data = self.data.clone();
+        for rgba in data.chunks_exact_mut(4) {
+            let [h, s, v] = rgb_to_hsv([rgba[0], rgba[1], rgba[2]]);
+            let rgb = hsv_to_rgb([(h + degrees).rem_euclid(360.0), s, v]);
+            rgba[..3].copy_from_slice(&rgb);
+        }
+
+        Self {
+            size: self.size,
+            data,
+        }
     }
 
-    fn image_format(&self) -> Result<nutexb::NutexbFormat, Box<dyn std::error::Error>> {
-        Ok(NutexbFormat::R8G8B8A8Unorm)
+    /// Blends the RGB values toward the identity LUT to reduce the strength of the effect.
+    /// A `strength` of `0.0` is the identity LUT, and `1.0` leaves the LUT unchanged.
+    pub fn scale_strength(&self, strength: f32) -> Self {
+        self.scale_strength_with_curve(strength, 0.0)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::create_default_lut_f32;
+    /// Blends toward the identity LUT like [Lut3dLinear::scale_strength]
+    /// but reduces the strength for dark and bright inputs based on `curve`.
+    /// A `curve` of `0.0` is linear, and `1.0` fades out the effect completely for black and white
+    /// while keeping the full `strength` for midtones.
+    pub fn scale_strength_with_curve(&self, strength: f32, curve: f32) -> Self {
+        let mut data = self.data.clone();
+        for z in 0..self.size {
+            for y in 0..self.size {
+                for x in 0..self.size {
+                    let input = node_coord(x, y, z, self.size);
 
-    use super::*;
+                    // Use the input luminance to weight the blend with 1.0 for midtones.
+                    let luma = 0.2126 * input[0] + 0.7152 * input[1] + 0.0722 * input[2];
+                    let weight = 1.0 - curve * (2.0 * luma - 1.0).powi(2);
+                    let t = strength * weight;
 
-    use indoc::indoc;
+                    let i = index3d(x, y, z, self.size, self.size) * 4;
+                    for c in 0..3 {
+                        data[i + c] = input[c] * (1.0 - t) + data[i + c] * t;
+                    }
+                }
+            }
+        }
 
-    #[test]
-    fn cube_to_linear() {
-        let text = indoc! {r#"
-            # comment
+        Self {
+            size: self.size,
+            data,
+        }
+    }
 
-            LUT_3D_SIZE 2
+    /// The RGB values for the nodes where the input red, green, and blue are equal.
+    /// This shows how the LUT affects the brightness and contrast of neutral colors.
+    pub fn neutral_ramp(&self) -> Vec<[f32; 3]> {
+        (0..self.size)
+            .map(|i| {
+                let offset = index3d(i, i, i, self.size, self.size) * 4;
+                [
+                    self.data[offset],
+                    self.data[offset + 1],
+                    self.data[offset + 2],
+                ]
+            })
+            .collect()
+    }
 
-            # comment
-            0 0 0
-            1 0 0
-            0 .75 0
-            1 .75 0
-            0 .25 1
-            1 .25 1
-            0 1 1
-            1 1 1
-        "#};
-        let cube = CubeLut3d::from_text(text).unwrap();
-        let linear = Lut3dLinear::from(cube);
+    /// Samples the LUT with trilinear interpolation at `steps` evenly spaced points from `start` to `end`.
+    /// The first and last samples are at `start` and `end`, and a single step only samples `start`.
+    /// This shows how the LUT affects a transition between two colors like the shadows and highlights of a skin tone.
+    pub fn sample_line(&self, start: [f32; 3], end: [f32; 3], steps: usize) -> Vec<[f32; 4]> {
+        (0..steps)
+            .map(|i| {
+                let t = if steps > 1 {
+                    i as f32 / (steps - 1) as f32
+                } else {
+                    0.0
+                };
+                // Interpolate so that the endpoints are exact.
+                let [r, g, b] = [0, 1, 2].map(|c| start[c] * (1.0 - t) + end[c] * t);
+                self.sample_rgba_trilinear(r, g, b)
+            })
+            .collect()
+    }
 
-        assert_eq!(2, linear.size);
-        assert_eq!(
-            &linear.data,
-            &[
-                0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.75, 0.0, 1.0, 1.0, 0.75, 0.0, 1.0,
-                0.0, 0.25, 1.0, 1.0, 1.0, 0.25, 1.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
-            ],
-        )
+    /// Measures how the LUT changes the hue, saturation, and value of a set of reference colors.
+    /// The result is the mean absolute hue shift in degrees
+    /// followed by the mean change in HSV saturation and value.
+    /// Positive saturation values indicate the LUT makes colors more saturated on average.
+    pub fn neutral_shift(&self) -> [f32; 3] {
+        // Use the primary and secondary hues at a few saturation levels.
+        let mut references = Vec::new();
+        for hue in [0.0, 60.0, 120.0, 180.0, 240.0, 300.0] {
+            for saturation in [0.25, 0.5, 1.0] {
+                references.push(hsv_to_rgb([hue, saturation, 0.75]));
+            }
+        }
+
+        let mut shift = [0.0; 3];
+        for rgb in &references {
+            let [r, g, b, _] = self.sample_rgba_trilinear(rgb[0], rgb[1], rgb[2]);
+            let before = rgb_to_hsv(*rgb);
+            let after = rgb_to_hsv([r, g, b]);
+
+            // Hue wraps around, so use the shortest angle between hues.
+            let hue = (after[0] - before[0]).abs() % 360.0;
+            shift[0] += hue.min(360.0 - hue);
+            shift[1] += after[1] - before[1];
+            shift[2] += after[2] - before[2];
+        }
+
+        shift.map(|s| s / references.len() as f32)
     }
 
-    #[test]
-    fn linear_to_rgba() {
-        let data = crate::create_default_lut();
-        let linear = Lut3dLinear::from_rgba(16, data);
+    /// Returns `true` if every node maps its RGB coordinate to itself.
+    /// Values are compared with a tolerance of less than half an 8-bit step,
+    /// so an identity LUT read from 8-bit data is still considered the identity.
+    /// Alpha is ignored.
+    pub fn is_identity(&self) -> bool {
+        // Check a sparse subset of nodes first to quickly reject most LUTs.
+        // The full scan still runs if all the sampled nodes match.
+        let step = (self.size / 4).max(1);
+        for z in (0..self.size).step_by(step) {
+            for y in (0..self.size).step_by(step) {
+                for x in (0..self.size).step_by(step) {
+                    if !self.is_identity_node(x, y, z) {
+                        return false;
+                    }
+                }
+            }
+        }
 
-        let img = RgbaImage::try_from(linear).unwrap();
+        for z in 0..self.size {
+            for y in 0..self.size {
+                for x in 0..self.size {
+                    if !self.is_identity_node(x, y, z) {
+                        return false;
+                    }
+                }
+            }
+        }
 
-        assert_eq!(256u32, img.width());
-        assert_eq!(16u32, img.height());
+        true
+    }
 
-        // Make sure the pixel values were copied correctly.
-        let data = crate::create_default_lut();
-        assert_eq!(&data, img.as_flat_samples().samples);
+    fn is_identity_node(&self, x: usize, y: usize, z: usize) -> bool {
+        let i = index3d(x, y, z, self.size, self.size) * 4;
+        let expected = node_coord(x, y, z, self.size);
+        self.data[i..i + 3]
+            .iter()
+            .zip(expected)
+            .all(|(value, expected)| (value - expected).abs() < 0.5 / 255.0)
     }
 
-    #[test]
-    fn linear_ref_to_rgba() {
-        let data = crate::create_default_lut();
-        let linear = Lut3dLinear::from_rgba(16, data);
+    /// Returns `true` if the red, green, and blue outputs never decrease
+    /// when increasing the x, y, and z coordinates, respectively.
+    /// Decreases of less than half an 8-bit step are ignored like [Lut3dLinear::is_identity].
+    pub fn is_monotonic(&self) -> bool {
+        let strides = [1, self.size, self.size * self.size];
+        for z in 0..self.size {
+            for y in 0..self.size {
+                for x in 0..self.size {
+                    let node = index3d(x, y, z, self.size, self.size);
+                    for (c, (coord, stride)) in [x, y, z].iter().zip(strides).enumerate() {
+                        if coord + 1 < self.size {
+                            let current = self.data[node * 4 + c];
+                            let next = self.data[(node + stride) * 4 + c];
+                            if next < current - 0.5 / 255.0 {
+                                return false;
+                            }
+                        }
+                    }
+                }
+            }
+        }
 
-        let img = RgbaImage::try_from(&linear).unwrap();
+        true
+    }
 
-        assert_eq!(256u32, img.width());
-        assert_eq!(16u32, img.height());
+    /// Returns `true` if every node has the same RGB output like a LUT made from a blank image.
+    /// Differences of less than half an 8-bit step are ignored like [Lut3dLinear::is_identity].
+    pub fn is_constant(&self) -> bool {
+        match self.data.get(..3) {
+            Some(first) => self.data.chunks_exact(4).all(|rgba| {
+                rgba[..3]
+                    .iter()
+                    .zip(first)
+                    .all(|(value, first)| (value - first).abs() < 0.5 / 255.0)
+            }),
+            None => true,
+        }
+    }
 
-        // Make sure the pixel values were copied correctly.
-        let data = crate::create_default_lut();
-        assert_eq!(&data, img.as_flat_samples().samples);
+    pub fn set_rgba(&mut self, x: usize, y: usize, z: usize, rgba: [f32; 4]) {
+        let i = index3d(x, y, z, self.size, self.size);
+        self.data[i * 4..i * 4 + 4].copy_from_slice(&rgba);
     }
 
-    #[test]
-    fn rgba_ref_to_linear() {
-        let data = crate::create_default_lut();
-        let img = RgbaImage::from_raw(256, 16, data).unwrap();
-        let linear = Lut3dLinear::try_from(&img).unwrap();
+    /// Averages the values of each node for all of the `luts`.
+    /// Returns `None` if `luts` is empty or the LUTs don't all have the same size.
+    pub fn average(luts: &[Lut3dLinear]) -> Option<Self> {
+        let size = luts.first()?.size;
+        if luts.iter().any(|lut| lut.size != size) {
+            return None;
+        }
 
-        assert_eq!(16, linear.size);
-        assert_eq!(16 * 16 * 16 * 4, linear.data.len());
+        let mut data = vec![0.0; size * size * size * 4];
+        for lut in luts {
+            for (value, lut_value) in data.iter_mut().zip(&lut.data) {
+                *value += lut_value;
+            }
+        }
+        for value in &mut data {
+            *value /= luts.len() as f32;
+        }
 
-        // Make sure the pixel values were copied correctly.
-        let data = create_default_lut_f32();
-        assert_eq!(&data, &linear.data);
+        Some(Self { size, data })
     }
 
-    #[test]
-    fn rgba_to_linear() {
-        let data = crate::create_default_lut();
-        let img = RgbaImage::from_raw(256, 16, data).unwrap();
-        let linear = Lut3dLinear::try_from(img).unwrap();
+    /// Creates a LUT with the RGB values from `self` and alpha from the luma of `mask` multiplied by its alpha.
+    /// This supports both opaque grayscale masks and white masks with an alpha channel.
+    /// Returns an error if `mask` doesn't have the same size as `self`.
+    pub fn with_alpha_from(&self, mask: &Lut3dLinear) -> Result<Self, LutError> {
+        if mask.size != self.size {
+            return Err(LutError::InvalidDimensions(
+                "The mask LUT must have the same size as the RGB LUT.",
+            ));
+        }
 
-        assert_eq!(16, linear.size);
-        assert_eq!(16 * 16 * 16 * 4, linear.data.len());
+        let mut data = self.data.clone();
+        for (rgba, mask) in data.chunks_exact_mut(4).zip(mask.data.chunks_exact(4)) {
+            let luma = 0.2126 * mask[0] + 0.7152 * mask[1] + 0.0722 * mask[2];
+            rgba[3] = luma * mask[3];
+        }
 
-        // Make sure the pixel values were copied correctly.
-        let data = create_default_lut_f32();
-        assert_eq!(&data, &linear.data);
+        Ok(Self {
+            size: self.size,
+            data,
+        })
+    }
+
+    /// Creates a LUT that applies `self` and then applies `after` to the result.
+    /// The result has the same size as `self`, and `after` is sampled with trilinear interpolation.
+    /// Alpha is always 1.0.
+    pub fn compose(&self, after: &Lut3dLinear) -> Self {
+        let data = self
+            .data
+            .chunks_exact(4)
+            .flat_map(|rgba| {
+                let [r, g, b, _] = after.sample_rgba_trilinear(rgba[0], rgba[1], rgba[2]);
+                [r, g, b, 1.0]
+            })
+            .collect();
+
+        Self {
+            size: self.size,
+            data,
+        }
+    }
+
+    /// Linearly interpolates between `self` at a `factor` of `0.0` and `other` at a `factor` of `1.0`.
+    /// The result has the same size as `self`, and `other` is sampled with trilinear interpolation.
+    pub fn blend(&self, other: &Lut3dLinear, factor: f32) -> Self {
+        let mut data = self.data.clone();
+        for z in 0..self.size {
+            for y in 0..self.size {
+                for x in 0..self.size {
+                    let [r, g, b] = node_coord(x, y, z, self.size);
+                    let other_rgba = other.sample_rgba_trilinear(r, g, b);
+
+                    let i = index3d(x, y, z, self.size, self.size);
+                    for (value, other_value) in data[i * 4..i * 4 + 4].iter_mut().zip(other_rgba) {
+                        *value = *value * (1.0 - factor) + other_value * factor;
+                    }
+                }
+            }
+        }
+
+        Self {
+            size: self.size,
+            data,
+        }
+    }
+
+    /// Creates a LUT that produces `target` when applied after `original` like `original.compose(&delta)`.
+    /// This allows sharing a small correction for an existing LUT instead of a completely new LUT.
+    /// Colors that `original` can't produce extrapolate past the edges of `original` when inverting.
+    /// The result has the same size as `target`.
+    pub fn correction_delta(original: &Lut3dLinear, target: &Lut3dLinear) -> Self {
+        // delta(original(x)) = target(x), so delta(y) = target(original_inv(y)).
+        Self::from_fn(target.size, |y| {
+            let [r, g, b] = original.invert_point(y);
+            let [r, g, b, _] = target.sample_rgba_trilinear(r, g, b);
+            [r, g, b]
+        })
+    }
+
+    // Find the input that maps to the output rgb using Newton's method.
+    fn invert_point(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let sample = |x: [f32; 3]| {
+            let [r, g, b, _] = self.sample_rgba_trilinear(x[0], x[1], x[2]);
+            [r, g, b]
+        };
+
+        let mut x = rgb;
+        for _ in 0..INVERT_STEPS {
+            let fx = sample(x);
+            let residual = [fx[0] - rgb[0], fx[1] - rgb[1], fx[2] - rgb[2]];
+            if residual.iter().all(|r| r.abs() < 1e-6) {
+                break;
+            }
+
+            // Estimate the Jacobian with central differences.
+            let h = 1e-3;
+            let mut jacobian = [[0.0; 3]; 3];
+            for c in 0..3 {
+                let (mut lo, mut hi) = (x, x);
+                lo[c] -= h;
+                hi[c] += h;
+                let (f_lo, f_hi) = (sample(lo), sample(hi));
+                for (row, (lo, hi)) in jacobian.iter_mut().zip(f_lo.iter().zip(f_hi)) {
+                    row[c] = (hi - lo) / (2.0 * h);
+                }
+            }
+
+            match solve3(jacobian, residual) {
+                Some(step) => {
+                    for (value, step) in x.iter_mut().zip(step) {
+                        // Allow extrapolating slightly past the edges for colors outside the gamut.
+                        *value = (*value - step).clamp(-1.0, 2.0);
+                    }
+                }
+                None => break,
+            }
+        }
+        x
+    }
+
+    /// Applies the LUT to the RGB values of each pixel in `img` using trilinear interpolation.
+    /// Alpha is preserved from `img`.
+    /// Large images are processed on multiple threads if the `parallel` feature is enabled.
+    pub fn apply_to_image(&self, img: &RgbaImage) -> RgbaImage {
+        self.apply_to_image_with_opacity(img, 1.0)
+    }
+
+    /// Applies the LUT like [Lut3dLinear::apply_to_image] but blends each pixel with the original RGB values.
+    /// An `opacity` of `0.0` returns the original image, and `1.0` fully applies the LUT.
+    pub fn apply_to_image_with_opacity(&self, img: &RgbaImage, opacity: f32) -> RgbaImage {
+        let opacity = opacity.clamp(0.0, 1.0);
+
+        #[cfg(feature = "parallel")]
+        if img.width() * img.height() >= PARALLEL_PIXEL_COUNT {
+            return self.apply_to_image_parallel(img, opacity);
+        }
+
+        self.apply_to_image_serial(img, opacity)
+    }
+
+    /// Applies the LUT to a small bundled reference image with a range of hues, saturations, and grays.
+    /// The result is `size` by `size` pixels for showing a quick preview of the LUT in a GUI.
+    pub fn preview_thumbnail(&self, size: u32) -> RgbaImage {
+        let reference = image::load_from_memory(PREVIEW_REFERENCE)
+            .unwrap()
+            .into_rgba8();
+        let thumbnail = image::imageops::resize(&reference, size, size, FilterType::Triangle);
+        self.apply_to_image(&thumbnail)
+    }
+
+    fn apply_to_image_serial(&self, img: &RgbaImage, opacity: f32) -> RgbaImage {
+        let mut output = img.clone();
+        for pixel in output.pixels_mut() {
+            self.apply_to_pixel(&mut pixel.0, opacity);
+        }
+        output
+    }
+
+    #[cfg(feature = "parallel")]
+    fn apply_to_image_parallel(&self, img: &RgbaImage, opacity: f32) -> RgbaImage {
+        use rayon::prelude::*;
+
+        let mut output = img.clone();
+        // Chunks can't be empty even for images with a width of 0.
+        let row_len = img.width() as usize * 4;
+        output.par_chunks_mut(row_len.max(4)).for_each(|row| {
+            for pixel in row.chunks_exact_mut(4) {
+                self.apply_to_pixel(pixel.try_into().unwrap(), opacity);
+            }
+        });
+        output
+    }
+
+    fn apply_to_pixel(&self, pixel: &mut [u8; 4], opacity: f32) {
+        let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|u| u as f32 / 255.0);
+        let rgba = self.sample_rgba_trilinear(r, g, b);
+        for ((value, f), original) in pixel[..3].iter_mut().zip(rgba).zip([r, g, b]) {
+            let f = original * (1.0 - opacity) + f * opacity;
+            *value = (f.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+
+    /// Resamples the LUT to the given `size` using trilinear interpolation.
+    pub fn resample(&self, size: usize) -> Self {
+        self.resample_with(size, Interpolation::Trilinear)
+    }
+
+    /// Resamples the LUT to the given `size` using the specified `interpolation`.
+    pub fn resample_with(&self, size: usize, interpolation: Interpolation) -> Self {
+        // The operation can't be cancelled, so this never fails.
+        self.resample_cancellable(size, interpolation, &AtomicBool::new(false))
+            .unwrap()
+    }
+
+    /// Resamples the LUT like [Lut3dLinear::resample_with] but interpolates the RGB values in linear light.
+    /// This assumes the RGB values are sRGB encoded and avoids darkening blended colors when downsampling.
+    pub fn resample_linear_light(&self, size: usize, interpolation: Interpolation) -> Self {
+        let mut lut = Self {
+            size: self.size,
+            data: self.data.clone(),
+        };
+        lut.map_rgb(linear);
+
+        let mut result = lut.resample_with(size, interpolation);
+        result.map_rgb(srgb);
+        result
+    }
+
+    /// Converts the RGB values from a .cube file with the given `color_space`.
+    /// Alpha is preserved.
+    pub fn convert_cube_color_space(&self, color_space: CubeColorSpace) -> Self {
+        let mut lut = Self {
+            size: self.size,
+            data: self.data.clone(),
+        };
+        match color_space {
+            CubeColorSpace::Data => (),
+            CubeColorSpace::Srgb => lut.map_rgb(linear),
+            CubeColorSpace::Linear => lut.map_rgb(srgb),
+        }
+        lut
+    }
+
+    fn map_rgb(&mut self, f: fn(f32) -> f32) {
+        for rgba in self.data.chunks_exact_mut(4) {
+            for value in &mut rgba[..3] {
+                *value = f(*value);
+            }
+        }
+    }
+
+    /// Resamples the LUT like [Lut3dLinear::resample_with].
+    /// Returns [Cancelled] shortly after `cancel` is set to `true` from another thread.
+    pub fn resample_cancellable(
+        &self,
+        size: usize,
+        interpolation: Interpolation,
+        cancel: &AtomicBool,
+    ) -> Result<Self, Cancelled> {
+        self.resample_until(size, interpolation, || cancel.load(Ordering::Relaxed))
+    }
+
+    // Checks for cancellation once for each z slice.
+    fn resample_until<F: FnMut() -> bool>(
+        &self,
+        size: usize,
+        interpolation: Interpolation,
+        mut is_cancelled: F,
+    ) -> Result<Self, Cancelled> {
+        let mut result = Self::empty_rgba(size);
+        for z in 0..size {
+            if is_cancelled() {
+                return Err(Cancelled);
+            }
+
+            for y in 0..size {
+                for x in 0..size {
+                    let [u, v, w] = node_coord(x, y, z, size);
+                    let rgba = self.sample_rgba(u, v, w, interpolation);
+                    result.set_rgba(x, y, z, rgba);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Samples a point in the LUT using 3D coordinates in the range `0.0` to `1.0`.
+    /// Coordinate values outside this range are preserved.
+    pub fn sample_rgba_trilinear(&self, x: f32, y: f32, z: f32) -> [f32; 4] {
+        let (corners, local) = self.region(x, y, z);
+
+        let mut result = [0.0; 4];
+        for (c, component) in result.iter_mut().enumerate() {
+            let fxyz = corners.map(|i| self.data[i * 4 + c]);
+            *component = trilinear(local, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, fxyz);
+        }
+
+        result
+    }
+
+    /// Returns the node indices and weights used by [Lut3dLinear::sample_rgba_trilinear] for the given point.
+    /// Node indices are in the same order as [Lut3dLinear::data] without the factor of 4 for channels.
+    /// Binary indices for the corners are fzyx in row-major order.
+    pub fn trilinear_neighbors(&self, x: f32, y: f32, z: f32) -> ([usize; 8], [f32; 8]) {
+        let (corners, (x, y, z)) = self.region(x, y, z);
+
+        let mut weights = [0.0; 8];
+        for (i, weight) in weights.iter_mut().enumerate() {
+            let wx = if i & 0b001 != 0 { x } else { 1.0 - x };
+            let wy = if i & 0b010 != 0 { y } else { 1.0 - y };
+            let wz = if i & 0b100 != 0 { z } else { 1.0 - z };
+            *weight = wx * wy * wz;
+        }
+
+        (corners, weights)
+    }
+
+    /// Samples a point in the LUT like [Lut3dLinear::sample_rgba_trilinear]
+    /// but interpolates only the 4 nodes of the tetrahedron containing the point.
+    pub fn sample_rgba_tetrahedral(&self, x: f32, y: f32, z: f32) -> [f32; 4] {
+        let (corners, local) = self.region(x, y, z);
+
+        let mut result = [0.0; 4];
+        for (c, component) in result.iter_mut().enumerate() {
+            let fxyz = corners.map(|i| self.data[i * 4 + c]);
+            *component = tetrahedral(local, fxyz);
+        }
+
+        result
+    }
+
+    /// Samples the node closest to a point in the LUT using 3D coordinates in the range `0.0` to `1.0`.
+    /// Coordinates outside this range are clamped.
+    pub fn sample_rgba_nearest(&self, x: f32, y: f32, z: f32) -> [f32; 4] {
+        let [x, y, z] = [x, y, z].map(|c| {
+            let (node, fraction) = coord_to_node(c, self.size);
+            (node + fraction.round().max(0.0) as usize).min(self.size - 1)
+        });
+
+        let i = index3d(x, y, z, self.size, self.size) * 4;
+        [
+            self.data[i],
+            self.data[i + 1],
+            self.data[i + 2],
+            self.data[i + 3],
+        ]
+    }
+
+    /// Samples a point in the 2D slice at index `z` with bilinear interpolation.
+    /// The `x` and `y` coordinates are in the range `0.0` to `1.0` like [Lut3dLinear::sample_rgba_trilinear].
+    /// Values of `z` past the last slice use the last slice.
+    pub fn sample_slice_bilinear(&self, z: usize, x: f32, y: f32) -> [f32; 4] {
+        let z = z.min(self.size - 1);
+
+        // Find the 2x2 region like sample_rgba_trilinear to extrapolate outside the slice.
+        let (x0, local_x) = coord_to_node(x, self.size);
+        let x1 = (x0 + 1).min(self.size - 1);
+        let (y0, local_y) = coord_to_node(y, self.size);
+        let y1 = (y0 + 1).min(self.size - 1);
+        let local = (local_x, local_y);
+
+        let corners = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)]
+            .map(|(x, y)| index3d(x, y, z, self.size, self.size));
+
+        let mut result = [0.0; 4];
+        for (c, component) in result.iter_mut().enumerate() {
+            let fxy = corners.map(|i| self.data[i * 4 + c]);
+            *component = bilinear(local, 0.0, 1.0, 0.0, 1.0, fxy);
+        }
+        result
+    }
+
+    /// Samples a point in the LUT using the specified `interpolation`.
+    pub fn sample_rgba(&self, x: f32, y: f32, z: f32, interpolation: Interpolation) -> [f32; 4] {
+        match interpolation {
+            Interpolation::Nearest => self.sample_rgba_nearest(x, y, z),
+            Interpolation::Trilinear => self.sample_rgba_trilinear(x, y, z),
+            Interpolation::Tetrahedral => self.sample_rgba_tetrahedral(x, y, z),
+        }
+    }
+
+    /// Returns the largest difference between the trilinear and tetrahedral samples for any RGB channel.
+    /// Colors with a large residual are sensitive to the interpolation used in game.
+    /// The residual is zero at the LUT nodes.
+    pub fn interpolation_residual(&self, rgb: [f32; 3]) -> f32 {
+        let [r, g, b] = rgb;
+        let trilinear = self.sample_rgba_trilinear(r, g, b);
+        let tetrahedral = self.sample_rgba_tetrahedral(r, g, b);
+        trilinear[..3]
+            .iter()
+            .zip(&tetrahedral[..3])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max)
+    }
+
+    // Find the node indices for the 2x2x2 region containing the xyz coordinate.
+    // Binary indices are fzyx in row-major order.
+    // Also returns the position relative to the start of the region.
+    fn region(&self, x: f32, y: f32, z: f32) -> ([usize; 8], (f32, f32, f32)) {
+        // TODO: Does this work for an empty lut?
+        // Find the endpoints of the 2x2 region containing the xyz coordinate.
+        // Interpolate using the position relative to the start of the region.
+        let (x0, local_x) = coord_to_node(x, self.size);
+        let x1 = (x0 + 1).min(self.size - 1);
+
+        let (y0, local_y) = coord_to_node(y, self.size);
+        let y1 = (y0 + 1).min(self.size - 1);
+
+        let (z0, local_z) = coord_to_node(z, self.size);
+        let z1 = (z0 + 1).min(self.size - 1);
+
+        let local = (local_x, local_y, local_z);
+
+        let corners = [
+            index3d(x0, y0, z0, self.size, self.size),
+            index3d(x1, y0, z0, self.size, self.size),
+            index3d(x0, y1, z0, self.size, self.size),
+            index3d(x1, y1, z0, self.size, self.size),
+            index3d(x0, y0, z1, self.size, self.size),
+            index3d(x1, y0, z1, self.size, self.size),
+            index3d(x0, y1, z1, self.size, self.size),
+            index3d(x1, y1, z1, self.size, self.size),
+        ];
+
+        (corners, local)
+    }
+}
+
+impl From<CubeLut3d> for Lut3dLinear {
+    fn from(value: CubeLut3d) -> Self {
+        let mut data = Vec::new();
+
+        for (r, g, b) in value.data {
+            // Always use 1.0 for alpha to match in game nutexb LUTs.
+            data.push(r);
+            data.push(g);
+            data.push(b);
+            data.push(1.0);
+        }
+
+        Lut3dLinear {
+            size: value.size as usize,
+            data,
+        }
+        .remap_domain(value.domain_min, value.domain_max)
+    }
+}
+
+impl TryFrom<RgbaImage> for Lut3dLinear {
+    type Error = LutError;
+
+    /// Tries to convert an image with slices in z arranged horizontally along the top of the image.
+    /// For example, a 16x16x16 LUT image must have dimensions at least 256x16 pixels.
+    fn try_from(value: RgbaImage) -> Result<Self, Self::Error> {
+        (&value).try_into()
+    }
+}
+
+impl TryFrom<&RgbaImage> for Lut3dLinear {
+    type Error = LutError;
+
+    /// Tries to convert an image with slices in z arranged horizontally along the top of the image.
+    /// For example, a 16x16x16 LUT image must have dimensions at least 256x16 pixels.
+    fn try_from(value: &RgbaImage) -> Result<Self, Self::Error> {
+        if value.width() != value.height() * value.height() {
+            return Err(LutError::InvalidDimensions(
+                "Invalid dimensions. Expected width to equal height * height.",
+            ));
+        }
+
+        // Buffers created with ImageBuffer::from_raw can be longer than the image.
+        let size = value.height() as usize;
+        let samples = value.as_flat_samples().samples;
+        let expected = size * size * size * 4;
+        if samples.len() != expected {
+            return Err(LutError::DataLengthMismatch {
+                size,
+                expected,
+                actual: samples.len(),
+            });
+        }
+
+        Ok(Lut3dLinear::from_rgba(size, samples.to_vec()))
+    }
+}
+
+impl TryFrom<Lut3dLinear> for RgbaImage {
+    type Error = LutError;
+
+    fn try_from(value: Lut3dLinear) -> Result<Self, Self::Error> {
+        Self::try_from(&value)
+    }
+}
+
+impl TryFrom<&Lut3dLinear> for RgbaImage {
+    type Error = LutError;
+
+    fn try_from(value: &Lut3dLinear) -> Result<Self, Self::Error> {
+        RgbaImage::from_raw(
+            (value.size * value.size) as u32,
+            value.size as u32,
+            value.to_rgba(),
+        )
+        .ok_or(LutError::InvalidDimensions("Error creating RgbaImage."))
+    }
+}
+
+impl TryFrom<&ImageBuffer<Rgba<u16>, Vec<u16>>> for Lut3dLinear {
+    type Error = LutError;
+
+    /// Tries to convert a 16-bit image with the same layout as [RgbaImage].
+    fn try_from(value: &ImageBuffer<Rgba<u16>, Vec<u16>>) -> Result<Self, Self::Error> {
+        if value.width() != value.height() * value.height() {
+            Err(LutError::InvalidDimensions(
+                "Invalid dimensions. Expected width to equal height * height.",
+            ))
+        } else {
+            Ok(Lut3dLinear::from_rgba16(
+                value.height() as usize,
+                value.as_flat_samples().samples.to_vec(),
+            ))
+        }
+    }
+}
+
+impl TryFrom<&Lut3dLinear> for ImageBuffer<Rgba<u16>, Vec<u16>> {
+    type Error = LutError;
+
+    fn try_from(value: &Lut3dLinear) -> Result<Self, Self::Error> {
+        ImageBuffer::from_raw(
+            (value.size * value.size) as u32,
+            value.size as u32,
+            value.to_rgba16(),
+        )
+        .ok_or(LutError::InvalidDimensions("Error creating ImageBuffer."))
+    }
+}
+
+impl ToNutexb for Lut3dLinear {
+    fn width(&self) -> u32 {
+        self.size as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.size as u32
+    }
+
+    fn depth(&self) -> u32 {
+        self.size as u32
+    }
+
+    fn image_data(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        // Sizes that can't be swizzled would produce a corrupt texture.
+        validate_nutexb_lut(self)?;
+        Ok(self.to_rgba())
+    }
+
+    fn mipmap_count(&self) -> u32 {
+        1
+    }
+
+    fn layer_count(&self) -> u32 {
+        1
+    }
+
+    fn image_format(&self) -> Result<nutexb::NutexbFormat, Box<dyn std::error::Error>> {
+        Ok(NutexbFormat::R8G8B8A8Unorm)
+    }
+}
+
+/// A [Lut3dLinear] quantized with a particular [RoundingMode] when converting to nutexb.
+pub(crate) struct QuantizedLut<'a> {
+    pub lut: &'a Lut3dLinear,
+    pub rounding: RoundingMode,
+    pub encoding: ColorEncoding,
+}
+
+impl<'a> ToNutexb for QuantizedLut<'a> {
+    fn width(&self) -> u32 {
+        self.lut.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.lut.height()
+    }
+
+    fn depth(&self) -> u32 {
+        self.lut.depth()
+    }
+
+    fn image_data(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        validate_nutexb_lut(self.lut)?;
+        match self.encoding {
+            ColorEncoding::Unorm => Ok(self.lut.to_rgba_rounded(self.rounding)),
+            ColorEncoding::Srgb => {
+                let mut encoded = Lut3dLinear {
+                    size: self.lut.size,
+                    data: self.lut.data.clone(),
+                };
+                for rgba in encoded.data.chunks_exact_mut(4) {
+                    for value in &mut rgba[..3] {
+                        *value = srgb(*value);
+                    }
+                }
+                Ok(encoded.to_rgba_rounded(self.rounding))
+            }
+        }
+    }
+
+    fn mipmap_count(&self) -> u32 {
+        1
+    }
+
+    fn layer_count(&self) -> u32 {
+        1
+    }
+
+    fn image_format(&self) -> Result<nutexb::NutexbFormat, Box<dyn std::error::Error>> {
+        Ok(self.encoding.format())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_default_lut_f32, create_identity_lut_f32};
+
+    use super::*;
+
+    use indoc::indoc;
+
+    #[test]
+    fn cube_to_linear() {
+        let text = indoc! {r#"
+            # comment
+
+            LUT_3D_SIZE 2
+
+            # comment
+            0 0 0
+            1 0 0
+            0 .75 0
+            1 .75 0
+            0 .25 1
+            1 .25 1
+            0 1 1
+            1 1 1
+        "#};
+        let cube = CubeLut3d::from_text(text).unwrap();
+        let linear = Lut3dLinear::from(cube);
+
+        assert_eq!(2, linear.size);
+        assert_eq!(
+            &linear.data,
+            &[
+                0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.75, 0.0, 1.0, 1.0, 0.75, 0.0, 1.0,
+                0.0, 0.25, 1.0, 1.0, 1.0, 0.25, 1.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+            ],
+        )
+    }
+
+    #[test]
+    fn cube_to_linear_to_cube() {
+        // Use distinct values for every channel to catch swapped channels or nodes.
+        let text = indoc! {r#"
+            TITLE "round trip"
+            LUT_3D_SIZE 2
+            0.01 0.02 0.03
+            0.11 0.12 0.13
+            0.21 0.22 0.23
+            0.31 0.32 0.33
+            0.41 0.42 0.43
+            0.51 0.52 0.53
+            0.61 0.62 0.63
+            0.71 0.72 0.73
+        "#};
+        let cube = CubeLut3d::from_text(text).unwrap();
+        let linear = Lut3dLinear::from_cube(&cube, AxisOrder::RedFastest).unwrap();
+        assert_eq!(
+            [0.11, 0.12, 0.13, 1.0],
+            linear.sample_rgba_nearest(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            [0.61, 0.62, 0.63, 1.0],
+            linear.sample_rgba_nearest(0.0, 1.0, 1.0)
+        );
+
+        // The title isn't stored in the linear LUT.
+        let new_cube = CubeLut3d::from(&linear);
+        assert_eq!(cube.size, new_cube.size);
+        assert_eq!(cube.domain_min, new_cube.domain_min);
+        assert_eq!(cube.domain_max, new_cube.domain_max);
+        assert_eq!(cube.data, new_cube.data);
+    }
+
+    #[test]
+    fn cube_negative_domain_to_linear_to_cube() {
+        let text = indoc! {r#"
+            DOMAIN_MIN -0.1 -0.1 -0.1
+            DOMAIN_MAX 1.0 1.0 1.0
+            LUT_3D_SIZE 2
+            -0.1 -0.1 -0.1
+            1.0 -0.1 -0.1
+            -0.1 1.0 -0.1
+            1.0 1.0 -0.1
+            -0.1 -0.1 1.0
+            1.0 -0.1 1.0
+            -0.1 1.0 1.0
+            1.0 1.0 1.0
+        "#};
+        let cube = CubeLut3d::from_text(text).unwrap();
+        assert!(cube.validate_domain().is_ok());
+
+        // The nodes are remapped from the cube's domain to the input range 0.0 to 1.0.
+        let linear = Lut3dLinear::from(cube);
+        approx::assert_relative_eq!(
+            [0.0, 0.0, 0.0, 1.0][..],
+            linear.sample_rgba_trilinear(0.0, 0.0, 0.0)[..],
+            epsilon = 0.0001
+        );
+        approx::assert_relative_eq!(
+            [0.5, 0.0, 1.0, 1.0][..],
+            linear.sample_rgba_trilinear(0.5, 0.0, 1.0)[..],
+            epsilon = 0.0001
+        );
+
+        // Sampling at the domain edges returns the first and last nodes of the cube.
+        approx::assert_relative_eq!(
+            [-0.1, -0.1, -0.1, 1.0][..],
+            linear.sample_rgba_trilinear(-0.1, -0.1, -0.1)[..],
+            epsilon = 0.0001
+        );
+        approx::assert_relative_eq!(
+            [1.0, 1.0, 1.0, 1.0][..],
+            linear.sample_rgba_trilinear(1.0, 1.0, 1.0)[..],
+            epsilon = 0.0001
+        );
+
+        // Writing uses the default domain, so reading the file again samples the same values.
+        let new_cube = CubeLut3d::from(&linear);
+        assert_eq!((0.0, 0.0, 0.0), new_cube.domain_min);
+        assert_eq!((1.0, 1.0, 1.0), new_cube.domain_max);
+
+        let mut text = Vec::new();
+        new_cube.write(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let new_linear = Lut3dLinear::from(CubeLut3d::from_text(&text).unwrap());
+        approx::assert_relative_eq!(
+            [-0.1, -0.1, -0.1, 1.0][..],
+            new_linear.sample_rgba_trilinear(-0.1, -0.1, -0.1)[..],
+            epsilon = 0.0001
+        );
+        approx::assert_relative_eq!(linear.data[..], new_linear.data[..], epsilon = 0.0001);
+    }
+
+    #[test]
+    fn cube_empty_domain_to_linear() {
+        let mut cube = CubeLut3d::from(&Lut3dLinear::identity());
+        cube.domain_min = (0.0, 0.5, 0.0);
+        cube.domain_max = (1.0, 0.5, 1.0);
+        assert_eq!(Lut3dLinear::identity(), Lut3dLinear::from(cube));
+    }
+
+    #[test]
+    fn cube_domain_to_linear_from_cube() {
+        // Nodes spanning the inputs 0.5 to 1.0 with an output of twice the input.
+        let mut cube = CubeLut3d::from(&Lut3dLinear::from_fn(4, |rgb| {
+            rgb.map(|c| 2.0 * (0.5 + 0.5 * c))
+        }));
+        cube.domain_min = (0.5, 0.5, 0.5);
+
+        let linear = Lut3dLinear::from_cube(&cube, AxisOrder::RedFastest).unwrap();
+        let expected = Lut3dLinear::from_fn(4, |rgb| rgb.map(|c| 2.0 * c));
+        approx::assert_relative_eq!(expected.data[..], linear.data[..], epsilon = 0.0001);
+        assert_eq!(linear, Lut3dLinear::from(cube));
+    }
+
+    #[test]
+    fn cube_blue_fastest_to_linear() {
+        let text = indoc! {r#"
+            LUT_3D_SIZE 2
+            0 0 0
+            0 0 1
+            0 .75 0
+            0 .75 1
+            1 .25 0
+            1 .25 1
+            1 1 0
+            1 1 1
+        "#};
+        let cube = CubeLut3d::from_text(text).unwrap();
+        let linear = Lut3dLinear::from_cube(&cube, AxisOrder::BlueFastest).unwrap();
+
+        assert_eq!(2, linear.size);
+        assert_eq!(
+            &linear.data,
+            &[
+                0.0, 0.0, 0.0, 1.0, 1.0, 0.25, 0.0, 1.0, 0.0, 0.75, 0.0, 1.0, 1.0, 1.0, 0.0, 1.0,
+                0.0, 0.0, 1.0, 1.0, 1.0, 0.25, 1.0, 1.0, 0.0, 0.75, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+            ],
+        )
+    }
+
+    #[test]
+    fn cube_red_fastest_to_linear() {
+        let cube = CubeLut3d::from(&Lut3dLinear::default_stage());
+        assert_eq!(
+            Lut3dLinear::default_stage(),
+            Lut3dLinear::from_cube(&cube, AxisOrder::RedFastest).unwrap()
+        );
+    }
+
+    #[test]
+    fn cube_data_length_mismatch_to_linear() {
+        let cube = CubeLut3d::new(
+            "".into(),
+            2,
+            (0.0, 0.0, 0.0),
+            (1.0, 1.0, 1.0),
+            vec![(1.0, 1.0, 1.0); 7],
+        );
+        for order in [AxisOrder::RedFastest, AxisOrder::BlueFastest] {
+            assert!(matches!(
+                Lut3dLinear::from_cube(&cube, order),
+                Err(LutError::DataLengthMismatch {
+                    size: 2,
+                    expected: 32,
+                    actual: 28
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn linear_to_rgba() {
+        let data = crate::create_default_lut();
+        let linear = Lut3dLinear::from_rgba(16, data);
+
+        let img = RgbaImage::try_from(linear).unwrap();
+
+        assert_eq!(256u32, img.width());
+        assert_eq!(16u32, img.height());
+
+        // Make sure the pixel values were copied correctly.
+        let data = crate::create_default_lut();
+        assert_eq!(&data, img.as_flat_samples().samples);
+    }
+
+    #[test]
+    fn linear_ref_to_rgba() {
+        let data = crate::create_default_lut();
+        let linear = Lut3dLinear::from_rgba(16, data);
+
+        let img = RgbaImage::try_from(&linear).unwrap();
+
+        assert_eq!(256u32, img.width());
+        assert_eq!(16u32, img.height());
+
+        // Make sure the pixel values were copied correctly.
+        let data = crate::create_default_lut();
+        assert_eq!(&data, img.as_flat_samples().samples);
+    }
+
+    #[test]
+    fn rgba_ref_to_linear() {
+        let data = crate::create_default_lut();
+        let img = RgbaImage::from_raw(256, 16, data).unwrap();
+        let linear = Lut3dLinear::try_from(&img).unwrap();
+
+        assert_eq!(16, linear.size);
+        assert_eq!(16 * 16 * 16 * 4, linear.data.len());
+
+        // Make sure the pixel values were copied correctly.
+        let data = create_default_lut_f32();
+        assert_eq!(&data, &linear.data);
+    }
+
+    #[test]
+    fn rgba_to_linear() {
+        let data = crate::create_default_lut();
+        let img = RgbaImage::from_raw(256, 16, data).unwrap();
+        let linear = Lut3dLinear::try_from(img).unwrap();
+
+        assert_eq!(16, linear.size);
+        assert_eq!(16 * 16 * 16 * 4, linear.data.len());
+
+        // Make sure the pixel values were copied correctly.
+        let data = create_default_lut_f32();
+        assert_eq!(&data, &linear.data);
     }
 
     #[test]
@@ -299,12 +1592,486 @@ mod tests {
         let img = RgbaImage::from_raw(128, 32, data).unwrap();
         let linear = Lut3dLinear::try_from(&img);
 
+        assert!(matches!(linear, Err(LutError::InvalidDimensions(_))));
+    }
+
+    #[test]
+    fn rgba_to_linear_data_length_mismatch() {
+        // The dimensions are valid, but the buffer has an extra pixel.
+        let data = vec![0u8; 4 * 2 * 4 + 4];
+        let img = RgbaImage::from_raw(4, 2, data).unwrap();
+        let linear = Lut3dLinear::try_from(&img);
+
+        assert!(matches!(
+            linear,
+            Err(LutError::DataLengthMismatch {
+                size: 2,
+                expected: 32,
+                actual: 36
+            })
+        ));
+    }
+
+    fn rounded(value: f32, rounding: RoundingMode) -> Vec<u8> {
+        let lut = Lut3dLinear {
+            size: 1,
+            data: vec![value; 4],
+        };
+        lut.to_rgba_rounded(rounding)
+    }
+
+    #[test]
+    fn to_rgba_rounding_nearest() {
+        assert_eq!(vec![127u8; 4], rounded(0.498, RoundingMode::Nearest));
+        assert_eq!(
+            rounded(0.498, RoundingMode::Nearest),
+            rounded(0.498, RoundingMode::default())
+        );
+    }
+
+    #[test]
+    fn to_rgba_rounding_floor() {
+        assert_eq!(vec![126u8; 4], rounded(0.498, RoundingMode::Floor));
+    }
+
+    #[test]
+    fn to_rgba_rounding_stochastic() {
+        // 0.498 * 255 = 126.99, so most values should round up.
+        let values = rounded(0.498, RoundingMode::Stochastic);
+        assert!(values.iter().all(|v| *v == 126 || *v == 127));
+        assert_eq!(values, rounded(0.498, RoundingMode::Stochastic));
+    }
+
+    #[test]
+    fn to_rgba_rounding_out_of_range() {
+        assert_eq!(vec![0u8; 4], rounded(-0.5, RoundingMode::Nearest));
+        assert_eq!(vec![255u8; 4], rounded(1.5, RoundingMode::Stochastic));
+    }
+
+    #[test]
+    fn is_identity_identity() {
+        assert!(Lut3dLinear::identity().is_identity());
+        assert!(Lut3dLinear::from_rgba(16, Lut3dLinear::identity().to_rgba()).is_identity());
+    }
+
+    #[test]
+    fn is_identity_default_stage() {
+        assert!(!Lut3dLinear::default_stage().is_identity());
+    }
+
+    #[test]
+    fn is_identity_single_node_changed() {
+        // This node isn't part of the sparse subset checked first.
+        let mut lut = Lut3dLinear::identity();
+        lut.set_rgba(5, 7, 9, [5.0 / 15.0, 7.0 / 15.0, 0.5, 1.0]);
+        assert!(!lut.is_identity());
+    }
+
+    #[test]
+    fn from_matrix_identity() {
+        let identity = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        assert_eq!(
+            Lut3dLinear::identity(),
+            Lut3dLinear::from_matrix(16, identity, [0.0; 3])
+        );
+    }
+
+    #[test]
+    fn from_matrix_swap_red_blue() {
+        let swap = [[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]];
+        assert_eq!(
+            Lut3dLinear::identity().swap_channels([2, 1, 0]),
+            Lut3dLinear::from_matrix(16, swap, [0.0; 3])
+        );
+    }
+
+    #[test]
+    fn from_matrix_offset() {
+        let scale = [[0.5, 0.0, 0.0], [0.0, 0.5, 0.0], [0.0, 0.0, 0.5]];
+        let lut = Lut3dLinear::from_matrix(2, scale, [0.1, 0.2, 0.3]);
+        assert_eq!(&[0.1, 0.2, 0.3, 1.0], &lut.data[..4]);
+        assert_eq!(&[0.6, 0.7, 0.8, 1.0], &lut.data[28..]);
+    }
+
+    #[test]
+    fn to_hex_size2() {
+        let lut = Lut3dLinear::from_rgba(2, (0..32).collect());
+        assert_eq!(
+            indoc! {"
+                00010203 04050607
+                08090a0b 0c0d0e0f
+                10111213 14151617
+                18191a1b 1c1d1e1f
+            "},
+            lut.to_hex()
+        );
+    }
+
+    #[test]
+    fn from_hex_to_hex() {
+        let lut = Lut3dLinear::default_stage();
+        assert_eq!(lut, Lut3dLinear::from_hex(16, &lut.to_hex()).unwrap());
+    }
+
+    #[test]
+    fn from_hex_whitespace() {
+        let lut = Lut3dLinear::from_hex(1, " 00 33\n\tff ff \r\n").unwrap();
+        assert_eq!(vec![0u8, 51u8, 255u8, 255u8], lut.to_rgba());
+    }
+
+    #[test]
+    fn from_hex_invalid_digit() {
+        assert!(matches!(
+            Lut3dLinear::from_hex(1, "000000gg"),
+            Err(LutError::Parse(e)) if e == "Invalid hex digit."
+        ));
+    }
+
+    #[test]
+    fn from_hex_invalid_length() {
+        assert!(matches!(
+            Lut3dLinear::from_hex(1, "0000000"),
+            Err(LutError::Parse(e)) if e == "Hex data length does not agree with size."
+        ));
+    }
+
+    #[test]
+    fn clamp_to_unit_out_of_range() {
+        let mut lut = Lut3dLinear {
+            size: 1,
+            data: vec![-0.5, 1.5, 0.25, 2.0],
+        };
+        lut.clamp_to_unit();
+        assert_eq!(vec![0.0, 1.0, 0.25, 2.0], lut.data);
+        assert_eq!(vec![0u8, 255u8, 64u8, 255u8], lut.to_rgba());
+    }
+
+    #[test]
+    fn snap_to_byte_grid_round_trip() {
+        let mut lut = analytic_lut(8);
+        assert_ne!(lut, Lut3dLinear::from_rgba(lut.size, lut.to_rgba()));
+
+        lut.snap_to_byte_grid();
+        assert_eq!(lut, Lut3dLinear::from_rgba(lut.size, lut.to_rgba()));
+    }
+
+    #[test]
+    fn snap_to_byte_grid_values() {
+        let mut lut = Lut3dLinear {
+            size: 1,
+            data: vec![127.0 / 255.0 - 0.00002, -0.5, 1.5, 1.0],
+        };
+        lut.snap_to_byte_grid();
+        assert_eq!(vec![127.0 / 255.0, 0.0, 1.0, 1.0], lut.data);
+    }
+
+    #[test]
+    fn compose_identity() {
+        let lut = Lut3dLinear::default_stage();
+        let identity = Lut3dLinear::identity();
+        approx::assert_relative_eq!(
+            lut.data[..],
+            lut.compose(&identity).data[..],
+            epsilon = 0.0001
+        );
+        approx::assert_relative_eq!(
+            lut.data[..],
+            identity.compose(&lut).data[..],
+            epsilon = 0.0001
+        );
+    }
+
+    #[test]
+    fn compose_swap_channels() {
+        let identity = Lut3dLinear::identity();
+        let swap = identity.swap_channels([2, 1, 0]);
+        approx::assert_relative_eq!(
+            identity.data[..],
+            swap.compose(&swap).data[..],
+            epsilon = 0.0001
+        );
+    }
+
+    #[test]
+    fn resample_identity() {
+        let lut = Lut3dLinear::identity();
+        assert!(lut.resample(8).is_identity());
+        assert!(lut.resample(33).is_identity());
+    }
+
+    #[test]
+    fn resample_same_size() {
+        let lut = Lut3dLinear::default_stage();
+        approx::assert_relative_eq!(lut.data[..], lut.resample(16).data[..], epsilon = 0.0001);
+    }
+
+    #[test]
+    fn resample_cancelled() {
+        let lut = Lut3dLinear::identity();
+        let cancel = AtomicBool::new(true);
+        assert_eq!(
+            Err(Cancelled),
+            lut.resample_cancellable(64, Interpolation::Trilinear, &cancel)
+        );
+    }
+
+    #[test]
+    fn resample_cancelled_mid_run() {
+        let lut = Lut3dLinear::identity();
+        let cancel = AtomicBool::new(false);
+        let mut slices = 0;
+        let result = lut.resample_until(64, Interpolation::Trilinear, || {
+            // Cancel after some of the slices have already been resampled.
+            if slices == 4 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            slices += 1;
+            cancel.load(Ordering::Relaxed)
+        });
+        assert_eq!(Err(Cancelled), result);
+        assert_eq!(5, slices);
+    }
+
+    #[test]
+    fn content_hash_below_quantization() {
+        let lut = Lut3dLinear::default_stage();
+        let mut noisy = Lut3dLinear::default_stage();
+        for f in &mut noisy.data {
+            *f += 0.1 / 255.0;
+        }
+        assert_ne!(lut, noisy);
+        assert_eq!(lut.content_hash(), noisy.content_hash());
+    }
+
+    #[test]
+    fn content_hash_different_data() {
+        let lut = Lut3dLinear::default_stage();
+        let identity = Lut3dLinear::identity();
+        assert_ne!(lut.content_hash(), identity.content_hash());
+        assert_ne!(identity.content_hash(), identity.resample(8).content_hash());
+    }
+
+    fn analytic_lut(size: usize) -> Lut3dLinear {
+        // Apply a gamma curve and then desaturate so the channels aren't independent.
+        let mut data = create_identity_lut_f32(size);
+        for rgba in data.chunks_exact_mut(4) {
+            let [r, g, b] = [rgba[0], rgba[1], rgba[2]].map(|f| f.powf(2.2));
+            let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            rgba[0] = r * 0.7 + luma * 0.3;
+            rgba[1] = g * 0.7 + luma * 0.3;
+            rgba[2] = b * 0.7 + luma * 0.3;
+        }
+        Lut3dLinear { size, data }
+    }
+
+    // There is no tricubic Interpolation, so only the supported methods are compared.
+    fn resample_error(interpolation: Interpolation) -> (f32, f32) {
+        // Downsample an analytic LUT and upsample again to measure the reconstruction error.
+        let lut = analytic_lut(33);
+        let result = lut
+            .resample_with(9, interpolation)
+            .resample_with(33, interpolation);
+        let diff = crate::difference(&lut, &result).unwrap();
+        (diff.mean, diff.max)
+    }
+
+    #[test]
+    fn resample_quality_nearest() {
+        let (mean, max) = resample_error(Interpolation::Nearest);
+        assert!(mean < 0.05);
+        assert!(max < 0.2);
+    }
+
+    #[test]
+    fn resample_quality_trilinear() {
+        let (mean, max) = resample_error(Interpolation::Trilinear);
+        assert!(mean < 0.005);
+        assert!(max < 0.02);
+        assert!(mean < resample_error(Interpolation::Nearest).0);
+    }
+
+    #[test]
+    fn resample_quality_tetrahedral() {
+        let (mean, max) = resample_error(Interpolation::Tetrahedral);
+        assert!(mean < 0.005);
+        assert!(max < 0.02);
+        assert!(mean < resample_error(Interpolation::Nearest).0);
+    }
+
+    #[test]
+    fn sample_rgba_tetrahedral_matches_trilinear_on_identity() {
+        let lut = Lut3dLinear::identity();
+        for xyz in [
+            (0.0, 0.0, 0.0),
+            (0.2, 0.7, 0.4),
+            (0.9, 0.1, 0.55),
+            (1.0, 1.0, 1.0),
+        ] {
+            approx::assert_relative_eq!(
+                lut.sample_rgba_trilinear(xyz.0, xyz.1, xyz.2)[..],
+                lut.sample_rgba_tetrahedral(xyz.0, xyz.1, xyz.2)[..],
+                epsilon = 0.0001
+            );
+        }
+    }
+
+    #[test]
+    fn convert_cube_color_space_mid_gray() {
+        let lut = Lut3dLinear::from_fn(2, |_| [0.5; 3]);
+        let value = |color_space| lut.convert_cube_color_space(color_space).data[0];
+
+        assert_eq!(0.5, value(CubeColorSpace::Data));
+        approx::assert_relative_eq!(0.21404, value(CubeColorSpace::Srgb), epsilon = 0.0001);
+        approx::assert_relative_eq!(0.73536, value(CubeColorSpace::Linear), epsilon = 0.0001);
+        assert_eq!(
+            1.0,
+            lut.convert_cube_color_space(CubeColorSpace::Srgb).data[3]
+        );
+    }
+
+    #[test]
+    fn preview_thumbnail_dimensions() {
+        let lut = Lut3dLinear::default_stage();
+        for size in [1, 16, 32, 100] {
+            let thumbnail = lut.preview_thumbnail(size);
+            assert_eq!((size, size), thumbnail.dimensions());
+        }
+    }
+
+    #[test]
+    fn preview_thumbnail_identity() {
+        let thumbnail = Lut3dLinear::identity().preview_thumbnail(32);
+        let reference = image::load_from_memory(PREVIEW_REFERENCE).unwrap();
+        assert_eq!(reference.into_rgba8(), thumbnail);
+    }
+
+    #[test]
+    fn interpolation_residual_grid_nodes() {
+        let lut = Lut3dLinear::default_stage().rotate_hue(45.0);
+        for z in 0..lut.size {
+            for y in 0..lut.size {
+                for x in 0..lut.size {
+                    let residual = lut.interpolation_residual(node_coord(x, y, z, lut.size));
+                    assert!(residual < 1e-6, "{} at node {:?}", residual, (x, y, z));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn interpolation_residual_between_nodes() {
+        // Only the white corner is non zero.
+        let mut lut = Lut3dLinear::empty_rgba(2);
+        lut.set_rgba(1, 1, 1, [1.0; 4]);
+
+        // Trilinear averages all 8 corners, but tetrahedral only uses the black and white corners.
+        let residual = lut.interpolation_residual([0.5, 0.5, 0.5]);
+        assert_eq!(0.5 - 0.125, residual);
+
+        // Both methods are exact for a linear LUT.
+        assert!(Lut3dLinear::identity().interpolation_residual([0.53, 0.21, 0.87]) < 1e-6);
+    }
+
+    #[test]
+    fn sample_size_17() {
+        let lut = Lut3dLinear::from_fn(17, |rgb| rgb);
+        for [r, g, b] in [
+            [0.0, 0.0, 0.0],
+            [0.3, 0.55, 0.91],
+            [0.5, 0.5, 0.5],
+            [1.0, 1.0, 1.0],
+        ] {
+            for interpolation in [Interpolation::Trilinear, Interpolation::Tetrahedral] {
+                approx::assert_relative_eq!(
+                    [r, g, b, 1.0][..],
+                    lut.sample_rgba(r, g, b, interpolation)[..],
+                    epsilon = 0.0001
+                );
+            }
+        }
+
+        // Nearest snaps to one of the 17 nodes.
+        assert_eq!(
+            [0.5, 0.0625, 1.0, 1.0],
+            lut.sample_rgba_nearest(0.49, 0.07, 0.99)
+        );
+    }
+
+    #[test]
+    fn resample_and_compose_size_17() {
+        // Trilinear interpolation is exact for a LUT created from a matrix.
+        let matrix = [[0.8, 0.1, 0.05], [0.05, 0.85, 0.05], [0.05, 0.05, 0.9]];
+        let lut = Lut3dLinear::from_matrix(17, matrix, [0.02; 3]);
+        let resampled = lut.resample(16);
+        let expected = Lut3dLinear::from_matrix(16, matrix, [0.02; 3]);
+        assert!(crate::difference(&resampled, &expected).unwrap().max < 0.0001);
+
+        let composed = Lut3dLinear::from_fn(17, |rgb| rgb).compose(&lut);
+        assert_eq!(17, composed.size);
+        assert!(crate::difference(&composed, &lut).unwrap().max < 0.0001);
+    }
+
+    #[test]
+    fn sample_rgba_nearest_2x2x2() {
+        let lut = Lut3dLinear {
+            size: 2,
+            data: create_identity_lut_f32(2),
+        };
+        assert_eq!([1.0, 0.0, 1.0, 1.0], lut.sample_rgba_nearest(0.6, 0.4, 1.5));
+        assert_eq!(
+            [0.0, 0.0, 0.0, 1.0],
+            lut.sample_rgba_nearest(-1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn sample_line_identity_diagonal() {
+        let line = Lut3dLinear::identity().sample_line([0.0; 3], [1.0; 3], 11);
+        assert_eq!(11, line.len());
+        for (i, rgba) in line.iter().enumerate() {
+            let expected = i as f32 / 10.0;
+            approx::assert_relative_eq!(
+                [expected, expected, expected, 1.0][..],
+                rgba[..],
+                epsilon = 0.0001
+            );
+        }
+    }
+
+    #[test]
+    fn sample_line_few_steps() {
+        let lut = Lut3dLinear::default_stage();
+        assert!(lut.sample_line([0.0; 3], [1.0; 3], 0).is_empty());
+        assert_eq!(
+            vec![lut.sample_rgba_trilinear(0.2, 0.3, 0.4)],
+            lut.sample_line([0.2, 0.3, 0.4], [0.9, 0.1, 0.5], 1)
+        );
         assert_eq!(
-            linear,
-            Err("Invalid dimensions. Expected width to equal height * height.")
+            vec![
+                lut.sample_rgba_trilinear(0.2, 0.3, 0.4),
+                lut.sample_rgba_trilinear(0.9, 0.1, 0.5)
+            ],
+            lut.sample_line([0.2, 0.3, 0.4], [0.9, 0.1, 0.5], 2)
         );
     }
 
+    #[test]
+    fn neutral_ramp_identity() {
+        let ramp = Lut3dLinear::identity().neutral_ramp();
+        assert_eq!(16, ramp.len());
+        for (i, rgb) in ramp.iter().enumerate() {
+            let expected = i as f32 / 15.0;
+            assert_eq!(&[expected; 3], rgb);
+        }
+    }
+
+    #[test]
+    fn neutral_ramp_default_stage() {
+        let ramp = Lut3dLinear::default_stage().neutral_ramp();
+        assert_eq!([15.0 / 255.0; 3], ramp[1]);
+        assert_eq!([240.0 / 255.0; 3], ramp[14]);
+    }
+
     #[test]
     fn sample_rgba_trilinear_single_pixel() {
         let lut = Lut3dLinear {
@@ -343,4 +2110,675 @@ mod tests {
             lut.sample_rgba_trilinear(0.5, 0.5, 0.5)
         )
     }
+
+    fn srgb_gradient_lut(size: usize) -> Lut3dLinear {
+        // The values are linear in linear light but not after encoding to sRGB.
+        let mut data = create_identity_lut_f32(size);
+        for rgba in data.chunks_exact_mut(4) {
+            let [r, g, b] = [rgba[0], rgba[1], rgba[2]];
+            rgba[0] = srgb(0.5 * r + 0.5 * g);
+            rgba[1] = srgb(0.5 * g + 0.5 * b);
+            rgba[2] = srgb(0.5 * b + 0.5 * r);
+        }
+        Lut3dLinear { size, data }
+    }
+
+    #[test]
+    fn resample_linear_light_gradient() {
+        let lut = srgb_gradient_lut(33);
+        let expected = srgb_gradient_lut(16);
+
+        let error = |lut: &Lut3dLinear| crate::difference(lut, &expected).unwrap().max;
+        let error_linear_light = error(&lut.resample_linear_light(16, Interpolation::Trilinear));
+        let error_srgb = error(&lut.resample_with(16, Interpolation::Trilinear));
+        assert!(error_linear_light < error_srgb);
+        assert!(error_linear_light < 0.0001);
+    }
+
+    #[test]
+    fn resample_linear_light_same_size() {
+        let lut = analytic_lut(8);
+        approx::assert_relative_eq!(
+            &lut.data[..],
+            &lut.resample_linear_light(8, Interpolation::Trilinear).data[..],
+            epsilon = 0.0001
+        );
+    }
+
+    #[test]
+    fn rotate_hue_full_rotation() {
+        let lut = analytic_lut(8);
+        for degrees in [0.0, 360.0, -360.0] {
+            approx::assert_relative_eq!(
+                &lut.data[..],
+                &lut.rotate_hue(degrees).data[..],
+                epsilon = 1e-5
+            );
+        }
+    }
+
+    #[test]
+    fn rotate_hue_120_degrees() {
+        let lut = Lut3dLinear::identity().rotate_hue(120.0);
+
+        // Primary colors rotate to the next primary color.
+        let red = lut.sample_rgba_nearest(1.0, 0.0, 0.0);
+        approx::assert_relative_eq!(&[0.0, 1.0, 0.0, 1.0][..], &red[..], epsilon = 1e-5);
+        let green = lut.sample_rgba_nearest(0.0, 1.0, 0.0);
+        approx::assert_relative_eq!(&[0.0, 0.0, 1.0, 1.0][..], &green[..], epsilon = 1e-5);
+
+        // Neutral colors have no hue to rotate.
+        let gray = lut.sample_rgba_nearest(0.4, 0.4, 0.4);
+        approx::assert_relative_eq!(gray[0], gray[1], epsilon = 1e-6);
+        approx::assert_relative_eq!(gray[1], gray[2], epsilon = 1e-6);
+    }
+
+    #[test]
+    fn scale_strength_endpoints() {
+        let lut = analytic_lut(8);
+        let identity = Lut3dLinear {
+            size: 8,
+            data: create_identity_lut_f32(8),
+        };
+        approx::assert_relative_eq!(
+            &identity.data[..],
+            &lut.scale_strength(0.0).data[..],
+            epsilon = 1e-6
+        );
+        approx::assert_relative_eq!(
+            &lut.data[..],
+            &lut.scale_strength(1.0).data[..],
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn scale_strength_curve_neutral_is_linear() {
+        let lut = analytic_lut(8);
+        let identity = create_identity_lut_f32(8);
+
+        // The default curve should be a linear blend with the identity LUT.
+        let expected: Vec<_> = identity
+            .iter()
+            .zip(&lut.data)
+            .map(|(a, b)| a * 0.25 + b * 0.75)
+            .collect();
+        let curved = lut.scale_strength_with_curve(0.75, 0.0);
+        approx::assert_relative_eq!(&expected[..], &curved.data[..], epsilon = 1e-6);
+        assert_eq!(lut.scale_strength(0.75), curved);
+    }
+
+    #[test]
+    fn scale_strength_curve_shadows_highlights() {
+        let lut = analytic_lut(8);
+        let curved = lut.scale_strength_with_curve(1.0, 1.0);
+
+        // Black and white are unchanged, but the midtones keep most of the effect.
+        approx::assert_relative_eq!(&[0.0; 3][..], &curved.data[..3], epsilon = 1e-6);
+        approx::assert_relative_eq!(
+            &[1.0; 3][..],
+            &curved.data[curved.data.len() - 4..][..3],
+            epsilon = 1e-6
+        );
+        let mid = index3d(4, 4, 4, 8, 8) * 4;
+        let identity = create_identity_lut_f32(8);
+        assert!(
+            (curved.data[mid] - identity[mid]).abs() > 0.9 * (lut.data[mid] - identity[mid]).abs()
+        );
+    }
+
+    #[test]
+    fn is_constant_empty_rgba() {
+        assert!(Lut3dLinear::empty_rgba(16).is_constant());
+    }
+
+    #[test]
+    fn is_constant_identity() {
+        assert!(!Lut3dLinear::identity().is_constant());
+    }
+
+    #[test]
+    fn is_constant_near_same_color() {
+        let mut lut = Lut3dLinear::from_fn(4, |_| [0.5, 0.25, 0.75]);
+        assert!(lut.is_constant());
+
+        lut.data[20] += 0.1 / 255.0;
+        assert!(lut.is_constant());
+
+        lut.data[21] += 1.0 / 255.0;
+        assert!(!lut.is_constant());
+    }
+
+    #[test]
+    fn correction_delta_compose() {
+        let original = Lut3dLinear::from_matrix(
+            16,
+            [[0.8, 0.1, 0.05], [0.05, 0.85, 0.05], [0.05, 0.05, 0.9]],
+            [0.02; 3],
+        );
+        let target = analytic_lut(16);
+
+        let delta = Lut3dLinear::correction_delta(&original, &target);
+        let result = original.compose(&delta);
+        assert!(crate::difference(&result, &target).unwrap().max < 0.01);
+    }
+
+    #[test]
+    fn correction_delta_identity_original() {
+        let target = analytic_lut(16);
+        let delta = Lut3dLinear::correction_delta(&Lut3dLinear::identity(), &target);
+        approx::assert_relative_eq!(&target.data[..], &delta.data[..], epsilon = 1e-5);
+    }
+
+    #[test]
+    fn solve3_identity() {
+        let m = [[1.0, 0.0, 0.0], [0.0, 2.0, 0.0], [0.0, 0.0, 4.0]];
+        assert_eq!(Some([1.0, 1.0, 1.0]), solve3(m, [1.0, 2.0, 4.0]));
+        assert_eq!(None, solve3([[0.0; 3]; 3], [1.0; 3]));
+    }
+
+    fn test_image(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8, 128])
+        })
+    }
+
+    #[test]
+    fn apply_to_image_identity() {
+        let img = test_image(64, 32);
+        assert_eq!(img, Lut3dLinear::identity().apply_to_image(&img));
+    }
+
+    #[test]
+    fn apply_to_image_matches_sampling() {
+        let lut = analytic_lut(16);
+        let img = test_image(8, 8);
+        let output = lut.apply_to_image(&img);
+
+        for (input, output) in img.pixels().zip(output.pixels()) {
+            let [r, g, b] = [input[0], input[1], input[2]].map(|u| u as f32 / 255.0);
+            let expected = lut.sample_rgba_trilinear(r, g, b);
+            for c in 0..3 {
+                assert_eq!((expected[c] * 255.0).round() as u8, output[c]);
+            }
+            assert_eq!(input[3], output[3]);
+        }
+    }
+
+    #[test]
+    fn apply_to_image_with_opacity() {
+        let lut = analytic_lut(16);
+        let img = test_image(8, 8);
+        assert_eq!(img, lut.apply_to_image_with_opacity(&img, 0.0));
+        assert_eq!(
+            lut.apply_to_image(&img),
+            lut.apply_to_image_with_opacity(&img, 1.0)
+        );
+    }
+
+    #[test]
+    fn apply_to_image_with_half_opacity() {
+        let lut = analytic_lut(16);
+        let img = test_image(8, 8);
+        let full = lut.apply_to_image(&img);
+        let half = lut.apply_to_image_with_opacity(&img, 0.5);
+
+        for ((input, full), half) in img.pixels().zip(full.pixels()).zip(half.pixels()) {
+            for c in 0..3 {
+                let expected = (input[c] as f32 + full[c] as f32) / 2.0;
+                assert!((half[c] as f32 - expected).abs() <= 1.0);
+            }
+            assert_eq!(input[3], half[3]);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn apply_to_image_parallel_matches_serial() {
+        let lut = analytic_lut(16);
+        let img = test_image(512, 300);
+        assert_eq!(
+            lut.apply_to_image_serial(&img, 1.0),
+            lut.apply_to_image_parallel(&img, 1.0)
+        );
+        assert_eq!(
+            lut.apply_to_image_serial(&img, 1.0),
+            lut.apply_to_image(&img)
+        );
+    }
+
+    #[test]
+    fn from_fn_identity() {
+        let lut = Lut3dLinear::from_fn(16, |c| c);
+        assert_eq!(create_identity_lut_f32(16), lut.data);
+        assert_eq!(Lut3dLinear::identity(), lut);
+    }
+
+    #[test]
+    fn from_fn_node_order() {
+        // Red changes fastest followed by green and then blue.
+        let lut = Lut3dLinear::from_fn(2, |[r, g, b]| [r, 2.0 * g, 4.0 * b]);
+        assert_eq!(
+            vec![
+                0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 2.0, 0.0, 1.0, 1.0, 2.0, 0.0, 1.0,
+                0.0, 0.0, 4.0, 1.0, 1.0, 0.0, 4.0, 1.0, 0.0, 2.0, 4.0, 1.0, 1.0, 2.0, 4.0, 1.0,
+            ],
+            lut.data
+        );
+    }
+
+    #[test]
+    fn average_identity_and_gamma() {
+        let identity = Lut3dLinear::identity();
+        let gamma = analytic_lut(16);
+
+        // Two LUTs should average to the halfway point between them.
+        let average = Lut3dLinear::average(&[identity, gamma]).unwrap();
+        let (identity, gamma) = (Lut3dLinear::identity(), analytic_lut(16));
+        let expected: Vec<_> = identity
+            .data
+            .iter()
+            .zip(&gamma.data)
+            .map(|(a, b)| a * 0.5 + b * 0.5)
+            .collect();
+        assert_eq!(16, average.size);
+        approx::assert_relative_eq!(&expected[..], &average.data[..], epsilon = 1e-6);
+    }
+
+    #[test]
+    fn average_single_lut() {
+        let lut = analytic_lut(4);
+        let average = Lut3dLinear::average(&[analytic_lut(4)]).unwrap();
+        assert_eq!(lut, average);
+    }
+
+    #[test]
+    fn average_size_mismatch() {
+        assert_eq!(
+            None,
+            Lut3dLinear::average(&[Lut3dLinear::identity(), analytic_lut(8)])
+        );
+    }
+
+    #[test]
+    fn with_alpha_from_mask() {
+        let lut = Lut3dLinear::default_stage().rotate_hue(90.0);
+        let mut mask = Lut3dLinear::identity();
+        mask.set_rgba(0, 0, 1, [1.0, 1.0, 1.0, 0.25]);
+
+        let result = lut.with_alpha_from(&mask).unwrap();
+        for (i, (rgba, expected)) in result
+            .data
+            .chunks_exact(4)
+            .zip(lut.data.chunks_exact(4))
+            .enumerate()
+        {
+            assert_eq!(rgba[..3], expected[..3]);
+
+            let mask = &mask.data[i * 4..i * 4 + 4];
+            let luma = 0.2126 * mask[0] + 0.7152 * mask[1] + 0.0722 * mask[2];
+            assert_eq!(luma * mask[3], rgba[3]);
+        }
+
+        // The modified node uses the mask alpha, and the corners use the luma.
+        assert_eq!(0.25, result.sample_rgba_nearest(0.0, 0.0, 1.0 / 15.0)[3]);
+        assert_eq!(0.0, result.sample_rgba_nearest(0.0, 0.0, 0.0)[3]);
+        approx::assert_relative_eq!(1.0, result.sample_rgba_nearest(1.0, 1.0, 1.0)[3]);
+        approx::assert_relative_eq!(0.7152, result.sample_rgba_nearest(0.0, 1.0, 0.0)[3]);
+    }
+
+    #[test]
+    fn with_alpha_from_size_mismatch() {
+        let result = Lut3dLinear::identity().with_alpha_from(&Lut3dLinear::from_fn(8, |c| c));
+        assert!(matches!(result, Err(LutError::InvalidDimensions(_))));
+    }
+
+    #[test]
+    fn average_empty() {
+        assert_eq!(None, Lut3dLinear::average(&[]));
+    }
+
+    #[test]
+    fn from_dynamic_image_rgb8() {
+        let lut = Lut3dLinear::from_rgba(4, (0..=255).collect());
+        let rgba = RgbaImage::try_from(&lut).unwrap();
+        let img = DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).to_rgb8());
+
+        let new_lut = Lut3dLinear::from_dynamic_image(&img).unwrap();
+        for (new, old) in new_lut.data.chunks_exact(4).zip(lut.data.chunks_exact(4)) {
+            assert_eq!(&old[..3], &new[..3]);
+            assert_eq!(1.0, new[3]);
+        }
+    }
+
+    #[test]
+    fn from_dynamic_image_rgba8() {
+        let lut = Lut3dLinear::from_rgba(4, (0..=255).collect());
+        let img = DynamicImage::ImageRgba8(RgbaImage::try_from(&lut).unwrap());
+        assert_eq!(lut, Lut3dLinear::from_dynamic_image(&img).unwrap());
+    }
+
+    #[test]
+    fn from_dynamic_image_rgb16() {
+        let lut = analytic_lut(4);
+        let rgba = ImageBuffer::<Rgba<u16>, Vec<u16>>::try_from(&lut).unwrap();
+        let img = DynamicImage::ImageRgb16(DynamicImage::ImageRgba16(rgba).to_rgb16());
+
+        let new_lut = Lut3dLinear::from_dynamic_image(&img).unwrap();
+        approx::assert_relative_eq!(&lut.data[..], &new_lut.data[..], epsilon = 0.5 / 65535.0);
+    }
+
+    #[test]
+    fn from_dynamic_image_rgba16() {
+        let lut = analytic_lut(4);
+        let img = DynamicImage::ImageRgba16(ImageBuffer::try_from(&lut).unwrap());
+
+        let new_lut = Lut3dLinear::from_dynamic_image(&img).unwrap();
+        approx::assert_relative_eq!(&lut.data[..], &new_lut.data[..], epsilon = 0.5 / 65535.0);
+
+        // The values shouldn't be truncated to 8 bits.
+        let img8 = DynamicImage::ImageRgba8(img.to_rgba8());
+        assert_ne!(new_lut, Lut3dLinear::from_dynamic_image(&img8).unwrap());
+    }
+
+    #[test]
+    fn from_dynamic_image_rgba32f() {
+        let lut = analytic_lut(4);
+        let img =
+            DynamicImage::ImageRgba32F(ImageBuffer::from_raw(16, 4, lut.data.clone()).unwrap());
+        assert_eq!(lut, Lut3dLinear::from_dynamic_image(&img).unwrap());
+    }
+
+    #[test]
+    fn from_dynamic_image_luma8() {
+        let img = DynamicImage::ImageLuma8(image::GrayImage::from_pixel(4, 2, image::Luma([51])));
+        let lut = Lut3dLinear::from_dynamic_image(&img).unwrap();
+        assert_eq!(2, lut.size);
+        assert_eq!(&[0.2, 0.2, 0.2, 1.0], &lut.data[..4]);
+    }
+
+    #[test]
+    fn from_dynamic_image_invalid_dimensions() {
+        let img = DynamicImage::new_rgba8(4, 4);
+        assert!(matches!(
+            Lut3dLinear::from_dynamic_image(&img),
+            Err(LutError::InvalidDimensions(_))
+        ));
+    }
+
+    #[test]
+    fn from_rgb_f32_sets_alpha() {
+        let data: Vec<_> = (0..8 * 3).map(|i| i as f32 / 24.0).collect();
+        let lut = Lut3dLinear::from_rgb_f32(2, data.clone()).unwrap();
+        assert_eq!(2, lut.size);
+        for (rgba, rgb) in lut.data.chunks_exact(4).zip(data.chunks_exact(3)) {
+            assert_eq!(rgb, &rgba[..3]);
+            assert_eq!(1.0, rgba[3]);
+        }
+    }
+
+    #[test]
+    fn from_rgb_f32_invalid_length() {
+        assert!(matches!(
+            Lut3dLinear::from_rgb_f32(2, vec![0.0; 8 * 4]),
+            Err(LutError::InvalidDimensions(_))
+        ));
+    }
+
+    #[test]
+    fn sample_rgba_trilinear_upper_edge() {
+        let lut = analytic_lut(16);
+        assert_eq!(
+            lut.data[lut.data.len() - 4..],
+            lut.sample_rgba_trilinear(1.0, 1.0, 1.0)
+        );
+        assert_eq!(
+            lut.data[index3d(15, 0, 15, 16, 16) * 4..][..4],
+            lut.sample_rgba_trilinear(1.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn sample_slice_bilinear_center() {
+        let lut = Lut3dLinear {
+            size: 2,
+            data: vec![
+                0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0,
+                0.0, 0.2, 0.4, 1.0, 0.2, 0.4, 0.6, 1.0, 0.4, 0.6, 0.8, 1.0, 0.6, 0.8, 1.0, 1.0,
+            ],
+        };
+        approx::assert_relative_eq!(
+            &[0.3, 0.5, 0.7, 1.0][..],
+            &lut.sample_slice_bilinear(1, 0.5, 0.5)[..],
+            epsilon = 1e-6
+        );
+        assert_eq!([0.0, 0.0, 0.0, 1.0], lut.sample_slice_bilinear(0, 0.5, 0.5));
+    }
+
+    #[test]
+    fn sample_slice_bilinear_matches_trilinear() {
+        let lut = analytic_lut(9);
+        for z in 0..9 {
+            approx::assert_relative_eq!(
+                &lut.sample_rgba_trilinear(0.3, 0.8, z as f32 / 8.0)[..],
+                &lut.sample_slice_bilinear(z, 0.3, 0.8)[..],
+                epsilon = 1e-5
+            );
+        }
+    }
+
+    #[test]
+    fn trilinear_neighbors_interior() {
+        let lut = analytic_lut(9);
+        let (x, y, z) = (0.3, 0.55, 0.8);
+
+        let (nodes, weights) = lut.trilinear_neighbors(x, y, z);
+        approx::assert_relative_eq!(1.0, weights.iter().sum::<f32>(), epsilon = 1e-6);
+
+        // The weighted sum of the nodes should match the sampled value.
+        let mut expected = [0.0; 4];
+        for (node, weight) in nodes.iter().zip(weights) {
+            for (c, value) in expected.iter_mut().enumerate() {
+                *value += lut.data[node * 4 + c] * weight;
+            }
+        }
+        approx::assert_relative_eq!(
+            &expected[..],
+            &lut.sample_rgba_trilinear(x, y, z)[..],
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn rgba16_image_round_trip() {
+        let lut = analytic_lut(9);
+        let img = ImageBuffer::<Rgba<u16>, Vec<u16>>::try_from(&lut).unwrap();
+        assert_eq!(81, img.width());
+        assert_eq!(9, img.height());
+
+        let new_lut = Lut3dLinear::try_from(&img).unwrap();
+        approx::assert_relative_eq!(lut.data[..], new_lut.data[..], epsilon = 0.5 / 65535.0);
+    }
+
+    #[test]
+    fn neutral_shift_identity() {
+        let shift = Lut3dLinear::identity().neutral_shift();
+        approx::assert_relative_eq!(&[0.0; 3][..], &shift[..], epsilon = 1e-4);
+    }
+
+    #[test]
+    fn neutral_shift_desaturate() {
+        // Blend halfway to grayscale.
+        let lut = Lut3dLinear::from_matrix(
+            16,
+            [
+                [0.5 + 0.5 / 3.0, 0.5 / 3.0, 0.5 / 3.0],
+                [0.5 / 3.0, 0.5 + 0.5 / 3.0, 0.5 / 3.0],
+                [0.5 / 3.0, 0.5 / 3.0, 0.5 + 0.5 / 3.0],
+            ],
+            [0.0; 3],
+        );
+        let [hue, saturation, _] = lut.neutral_shift();
+        assert!(hue < 0.01);
+        assert!(saturation < -0.1);
+    }
+
+    #[test]
+    fn rgb_hsv_round_trip() {
+        for rgb in [
+            [1.0, 0.0, 0.0],
+            [0.2, 0.6, 0.4],
+            [0.5, 0.5, 0.5],
+            [0.1, 0.3, 0.9],
+        ] {
+            let hsv = rgb_to_hsv(rgb);
+            approx::assert_relative_eq!(&rgb[..], &hsv_to_rgb(hsv)[..], epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn posterize_levels_equal_size() {
+        // Identity nodes already lie on the posterized levels.
+        let lut = Lut3dLinear::identity();
+        approx::assert_relative_eq!(
+            lut.data[..],
+            lut.posterize(lut.size).data[..],
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn posterize_256_levels_8_bit() {
+        let lut = Lut3dLinear::default_stage();
+        assert_eq!(lut.to_rgba(), lut.posterize(256).to_rgba());
+    }
+
+    #[test]
+    fn posterize_two_levels() {
+        let lut = Lut3dLinear::from_matrix(
+            4,
+            [[0.5, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            [0.0; 3],
+        );
+        let posterized = lut.posterize(2);
+        assert!(posterized
+            .data
+            .chunks_exact(4)
+            .all(|c| c[..3].iter().all(|v| *v == 0.0 || *v == 1.0)));
+        assert_eq!(1.0, posterized.data[3]);
+    }
+
+    #[test]
+    fn from_image_sized_padded() {
+        let lut = Lut3dLinear::default_stage();
+        let strip = RgbaImage::try_from(&lut).unwrap();
+
+        let mut padded = RgbaImage::from_pixel(260, 20, image::Rgba([255, 0, 255, 255]));
+        image::imageops::replace(&mut padded, &strip, 0, 0);
+
+        assert!(Lut3dLinear::try_from(&padded).is_err());
+        assert_eq!(lut, Lut3dLinear::from_image_sized(&padded, 16).unwrap());
+    }
+
+    #[test]
+    fn from_dynamic_image_sized_rgba16_padded() {
+        // Use values that aren't exactly representable with 8 bits.
+        let lut = Lut3dLinear::from_matrix(
+            4,
+            [[0.9, 0.0, 0.0], [0.0, 0.9, 0.0], [0.0, 0.0, 0.9]],
+            [0.0123; 3],
+        );
+        let strip = image::ImageBuffer::from_raw(16, 4, lut.to_rgba16()).unwrap();
+        let mut padded = image::ImageBuffer::<Rgba<u16>, _>::new(20, 8);
+        image::imageops::replace(&mut padded, &strip, 0, 0);
+
+        let img = DynamicImage::ImageRgba16(padded);
+        let sized = Lut3dLinear::from_dynamic_image_sized(&img, 4).unwrap();
+        assert_eq!(lut.to_rgba16(), sized.to_rgba16());
+        assert_ne!(
+            lut.to_rgba16(),
+            Lut3dLinear::from_rgba(4, sized.to_rgba()).to_rgba16()
+        );
+    }
+
+    #[test]
+    fn from_dynamic_image_sized_too_small() {
+        let img = DynamicImage::new_rgba16(15, 4);
+        assert!(matches!(
+            Lut3dLinear::from_dynamic_image_sized(&img, 4),
+            Err(LutError::InvalidDimensions(_))
+        ));
+    }
+
+    #[test]
+    fn from_image_sized_too_small() {
+        let img = RgbaImage::new(255, 16);
+        assert!(matches!(
+            Lut3dLinear::from_image_sized(&img, 16),
+            Err(LutError::InvalidDimensions(_))
+        ));
+    }
+
+    #[test]
+    fn is_monotonic_identity() {
+        assert!(Lut3dLinear::identity().is_monotonic());
+        assert!(Lut3dLinear::default_stage().is_monotonic());
+    }
+
+    #[test]
+    fn is_monotonic_inverted() {
+        let lut = Lut3dLinear::from_matrix(
+            4,
+            [[-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            [1.0, 0.0, 0.0],
+        );
+        assert!(!lut.is_monotonic());
+    }
+
+    #[test]
+    fn is_monotonic_cross_channel() {
+        // Red decreasing along y doesn't affect monotonicity.
+        let lut = Lut3dLinear::from_matrix(
+            4,
+            [[1.0, -0.5, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            [0.5, 0.0, 0.0],
+        );
+        assert!(lut.is_monotonic());
+    }
+
+    #[test]
+    fn default_stage_cached() {
+        assert_eq!(Lut3dLinear::default_stage(), Lut3dLinear::default_stage());
+        assert_eq!(
+            Lut3dLinear::default_stage(),
+            Lut3dLinear {
+                size: 16,
+                data: create_default_lut_f32()
+            }
+        );
+        assert!(std::ptr::eq(
+            Lut3dLinear::default_stage_ref(),
+            Lut3dLinear::default_stage_ref()
+        ));
+    }
+
+    #[test]
+    fn blend_half() {
+        let a = Lut3dLinear::from_matrix(4, [[0.0; 3]; 3], [0.0; 3]);
+        let b = Lut3dLinear::from_matrix(2, [[0.0; 3]; 3], [1.0; 3]);
+        let lut = a.blend(&b, 0.5);
+        assert_eq!(4, lut.size);
+        assert!(lut
+            .data
+            .chunks_exact(4)
+            .all(|rgba| rgba == [0.5, 0.5, 0.5, 1.0]));
+    }
+
+    #[test]
+    fn blend_endpoints() {
+        let a = Lut3dLinear::default_stage();
+        let b = analytic_lut(16);
+        assert_eq!(a, a.blend(&b, 0.0));
+        approx::assert_relative_eq!(&b.data[..], &a.blend(&b, 1.0).data[..], epsilon = 1e-6);
+    }
 }
@@ -0,0 +1,159 @@
+use nutexb::{NutexbFormat, ToNutexb};
+
+use crate::{index3d, validate_nutexb_size, Lut3dLinear};
+
+/// A 3D RGB LUT with unswizzled data in row major order.
+/// This uses 3/4 the memory of [Lut3dLinear] since the alpha is always assumed to be 1.0.
+/// Values are written to data using a nested ZYX loops with X being the innermost loop.
+#[derive(Debug, PartialEq)]
+pub struct Lut3dRgb {
+    /// The dimensions for each axis.
+    pub size: usize,
+    pub data: Vec<f32>,
+}
+
+impl Lut3dRgb {
+    pub fn rgb(&self, x: usize, y: usize, z: usize) -> [f32; 3] {
+        let i = index3d(x, y, z, self.size, self.size) * 3;
+        [self.data[i], self.data[i + 1], self.data[i + 2]]
+    }
+
+    /// Quantizes the data to 8 bits per channel with an alpha of 255.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.data
+            .chunks_exact(3)
+            .flat_map(|c| [c[0], c[1], c[2], 1.0].map(|f| (f * 255.0).round() as u8))
+            .collect()
+    }
+}
+
+impl From<&Lut3dLinear> for Lut3dRgb {
+    fn from(value: &Lut3dLinear) -> Self {
+        Self {
+            size: value.size,
+            data: value
+                .data
+                .chunks_exact(4)
+                .flat_map(|c| [c[0], c[1], c[2]])
+                .collect(),
+        }
+    }
+}
+
+impl From<Lut3dLinear> for Lut3dRgb {
+    fn from(value: Lut3dLinear) -> Self {
+        (&value).into()
+    }
+}
+
+impl From<&Lut3dRgb> for Lut3dLinear {
+    fn from(value: &Lut3dRgb) -> Self {
+        // Always use 1.0 for alpha to match in game nutexb LUTs.
+        Self {
+            size: value.size,
+            data: value
+                .data
+                .chunks_exact(3)
+                .flat_map(|c| [c[0], c[1], c[2], 1.0])
+                .collect(),
+        }
+    }
+}
+
+impl From<Lut3dRgb> for Lut3dLinear {
+    fn from(value: Lut3dRgb) -> Self {
+        (&value).into()
+    }
+}
+
+impl ToNutexb for Lut3dRgb {
+    fn width(&self) -> u32 {
+        self.size as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.size as u32
+    }
+
+    fn depth(&self) -> u32 {
+        self.size as u32
+    }
+
+    fn image_data(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        // Sizes that can't be swizzled would produce a corrupt texture.
+        validate_nutexb_size(self.size)?;
+        Ok(self.to_rgba())
+    }
+
+    fn mipmap_count(&self) -> u32 {
+        1
+    }
+
+    fn layer_count(&self) -> u32 {
+        1
+    }
+
+    fn image_format(&self) -> Result<nutexb::NutexbFormat, Box<dyn std::error::Error>> {
+        Ok(NutexbFormat::R8G8B8A8Unorm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nutexb::NutexbFile;
+
+    use super::*;
+
+    #[test]
+    fn linear_to_rgb() {
+        let linear = Lut3dLinear::default_stage();
+        let rgb = Lut3dRgb::from(&linear);
+
+        assert_eq!(16, rgb.size);
+        assert_eq!(16 * 16 * 16 * 3, rgb.data.len());
+
+        let i = index3d(1, 2, 3, 16, 16) * 4;
+        assert_eq!(&linear.data[i..i + 3], &rgb.rgb(1, 2, 3)[..]);
+    }
+
+    #[test]
+    fn rgb_to_linear() {
+        let linear = Lut3dLinear::default_stage();
+        let rgb = Lut3dRgb::from(&linear);
+        assert_eq!(linear, Lut3dLinear::from(rgb));
+    }
+
+    #[test]
+    fn rgb_to_rgba() {
+        let linear = Lut3dLinear::default_stage();
+        let rgb = Lut3dRgb::from(&linear);
+        assert_eq!(linear.to_rgba(), rgb.to_rgba());
+    }
+
+    #[test]
+    fn rgb_to_nutexb() {
+        let linear = Lut3dLinear::default_stage();
+        let rgb = Lut3dRgb::from(&linear);
+
+        let expected = NutexbFile::create(&linear, "color_grading_lut").unwrap();
+        let actual = NutexbFile::create(&rgb, "color_grading_lut").unwrap();
+        assert_eq!(expected.data, actual.data);
+        assert_eq!(expected.footer, actual.footer);
+    }
+
+    #[test]
+    fn rgb_to_nutexb_unsupported_size() {
+        let rgb = Lut3dRgb::from(&Lut3dLinear::default_stage().resample(33));
+        assert!(NutexbFile::create(&rgb, "color_grading_lut").is_err());
+    }
+
+    #[test]
+    fn rgb_to_nutexb_size8() {
+        let linear = Lut3dLinear::default_stage().resample(8);
+        let rgb = Lut3dRgb::from(&linear);
+
+        let expected = NutexbFile::create(&linear, "color_grading_lut").unwrap();
+        let actual = NutexbFile::create(&rgb, "color_grading_lut").unwrap();
+        assert_eq!(expected.data, actual.data);
+    }
+}
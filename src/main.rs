@@ -1,115 +1,2069 @@
-use clap::{Arg, Command};
+use clap::{builder::RangedU64ValueParser, Arg, ArgMatches, Command};
+use image::{ImageBuffer, ImageFormat, Rgba};
 use std::{
     convert::TryFrom,
+    error::Error,
     fs::{self, File},
-    io::Write,
+    io::{BufWriter, Cursor, Write},
     path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, Instant},
 };
 
-use smush_lut::{correct_lut, Lut3dLinear};
+use smush_lut::{
+    correct_lut_with_params, correction_glsl, uncorrect_lut_with_params, ColorEncoding,
+    CubeColorSpace, CubeLut1d, Interpolation, Lut3dLinear, LutDifference, LutError, RoundingMode,
+    StageParams, TestPattern,
+};
 
 fn main() {
-    let matches = Command::new("smush_lut")
+    let matches = cli().get_matches();
+
+    match matches.subcommand() {
+        Some(("compare", sub_matches)) => compare(sub_matches),
+        Some(("duplicates", sub_matches)) => duplicates(sub_matches),
+        Some(("sequence", sub_matches)) => sequence(sub_matches),
+        Some(("posterize", sub_matches)) => posterize(sub_matches),
+        Some(("export", sub_matches)) => export(sub_matches),
+        Some(("tonecurve", sub_matches)) => tone_curve(sub_matches),
+        Some(("uncorrect", sub_matches)) => uncorrect(sub_matches),
+        Some(("resize", sub_matches)) => resize(sub_matches),
+        Some(("testpattern", sub_matches)) => test_pattern(sub_matches),
+        Some(("check-swizzle", sub_matches)) => check_swizzle(sub_matches),
+        Some(("params", sub_matches)) => params(sub_matches),
+        Some(("default-stage", sub_matches)) => {
+            let output = Path::new(sub_matches.value_of("output").unwrap());
+            if let Err(e) = save_output(&Lut3dLinear::default_stage(), output) {
+                eprintln!("Failed to write {:?}: {}", output, e);
+                std::process::exit(1);
+            }
+        }
+        _ => convert(&matches),
+    }
+}
+
+fn cli() -> Command<'static> {
+    Command::new("smush_lut")
         .version("0.3")
         .author("SMG")
         .about("Create 3D color grading LUTs for Smash Ultimate")
-        .arg(
-            Arg::new("input")
-                .index(1)
-                .help("the input image, .cube, or .nutexb file")
-                .required(true)
-                .takes_value(true),
-        )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .arg(input_arg())
         .arg(
             Arg::new("output")
                 .index(2)
-                .help("the output image, .cube, .nutexb, or .bin file")
+                .help(output_help())
                 .required(false)
                 .takes_value(true),
         )
+        .args(zip_args())
         .arg(
             Arg::new("raw")
                 .short('r')
                 .long("raw")
-                .help("Exports the raw LUT values without any stage LUT compensation")
+                .visible_alias("package-only")
+                .help("Exports the raw LUT values without any stage LUT compensation. Use this for LUTs that are already corrected for the game")
+                .required(false)
+                .takes_value(false),
+        )
+        .arg(
+            Arg::new("also-cube")
+                .long("also-cube")
+                .help("Also writes a .cube file next to the output with the same LUT values")
+                .required(false)
+                .takes_value(false),
+        )
+        .arg(
+            Arg::new("half-texel")
+                .long("half-texel")
+                .help(
+                    "Calculates stage LUT compensation at texel centers like GPU texture sampling",
+                )
+                .required(false)
+                .takes_value(false),
+        )
+        .arg(
+            Arg::new("accurate")
+                .long("accurate")
+                .help("Refines stage LUT compensation with a few iterations for slightly better precision")
                 .required(false)
                 .takes_value(false),
         )
-        .get_matches();
+        .arg(
+            Arg::new("stage-params")
+                .long("stage-params")
+                .help("A TOML or JSON file with the post processing parameters for stage LUT compensation")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("force-size")
+                .long("force-size")
+                .help("Reads image inputs as a LUT with this size from the top left size * size by size pixels instead of detecting the size from the dimensions. PNG files with --embed-source values and other formats ignore this")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("cube-colorspace")
+                .long("cube-colorspace")
+                .help("The encoding of .cube input values. srgb converts to linear, and linear converts to sRGB")
+                .required(false)
+                .takes_value(true)
+                .possible_values(["data", "srgb", "linear"])
+                .default_value("data"),
+        )
+        .arg(
+            Arg::new("bit-depth")
+                .long("bit-depth")
+                .help("The bits per channel for image outputs like .png")
+                .required(false)
+                .takes_value(true)
+                .possible_values(["8", "16"])
+                .default_value("8"),
+        )
+        .arg(
+            Arg::new("nutexb-format")
+                .long("nutexb-format")
+                .help("The texture format for .nutexb outputs. srgb stores sRGB encoded values that are converted to linear when sampled")
+                .required(false)
+                .takes_value(true)
+                .possible_values(["unorm", "srgb"])
+                .default_value("unorm"),
+        )
+        .arg(
+            Arg::new("embed-source")
+                .long("embed-source")
+                .help("Embeds the full precision LUT values in .png outputs for lossless reimporting")
+                .required(false)
+                .takes_value(false),
+        )
+        .arg(
+            Arg::new("check-monotonic")
+                .long("check-monotonic")
+                .help("Warns if stage LUT compensation produces values that decrease as the input increases")
+                .required(false)
+                .takes_value(false),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Prints a single JSON object with the conversion results or error")
+                .required(false)
+                .takes_value(false),
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Reports the difference between two LUTs after resampling to a common size")
+                .arg(
+                    Arg::new("a")
+                        .index(1)
+                        .help(input_help())
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("b")
+                        .index(2)
+                        .help(input_help())
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("threshold")
+                        .long("threshold")
+                        .help("Exits with an error if the max difference exceeds this value")
+                        .required(false)
+                        .takes_value(true)
+                        .value_parser(|s: &str| s.parse::<f32>()),
+                ),
+        )
+        .subcommand(
+            Command::new("duplicates")
+                .about("Reports groups of LUTs that produce the same 8-bit values for removing duplicates")
+                .arg(
+                    Arg::new("inputs")
+                        .index(1)
+                        .help(inputs_help())
+                        .required(true)
+                        .takes_value(true)
+                        .multiple_values(true),
+                ),
+        )
+        .subcommand(
+            Command::new("sequence")
+                .about("Combines LUTs in frame order into a single sequence file with one frame for each LUT for animated grades")
+                .arg(
+                    Arg::new("inputs")
+                        .index(1)
+                        .help(inputs_help())
+                        .required(true)
+                        .takes_value(true)
+                        .multiple_values(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .index(2)
+                        .help("the output .seq file")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("posterize")
+                .about("Quantizes each channel of a LUT to a number of levels for a banded look")
+                .arg(
+                    Arg::new("input")
+                        .index(1)
+                        .help(input_help())
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .index(2)
+                        .help(output_help())
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("levels")
+                        .long("levels")
+                        .help("The number of values for each channel")
+                        .required(false)
+                        .takes_value(true)
+                        .value_parser(RangedU64ValueParser::<usize>::new().range(2..))
+                        .default_value("8"),
+                ),
+        )
+        .subcommand(
+            Command::new("tonecurve")
+                .about("Writes the response of a LUT to neutral grays as a 1D .cube file")
+                .arg(
+                    Arg::new("input")
+                        .index(1)
+                        .help(input_help())
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .index(2)
+                        .help("the output 1D .cube file")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Writes a .cube file and a preview .png image of a LUT to a folder")
+                .arg(
+                    Arg::new("input")
+                        .index(1)
+                        .help(input_help())
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .index(2)
+                        .help("the output folder")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("cube-name")
+                        .long("cube-name")
+                        .help("The file name for the .cube file")
+                        .required(false)
+                        .takes_value(true)
+                        .default_value("lut.cube"),
+                )
+                .arg(
+                    Arg::new("preview-name")
+                        .long("preview-name")
+                        .help("The file name for the preview image")
+                        .required(false)
+                        .takes_value(true)
+                        .default_value("preview.png"),
+                ),
+        )
+        .subcommand(
+            Command::new("uncorrect")
+                .about("Recovers the edit LUT from a LUT with stage LUT compensation already applied")
+                .arg(
+                    Arg::new("input")
+                        .index(1)
+                        .help(input_help())
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .index(2)
+                        .help(output_help())
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("stage")
+                        .long("stage")
+                        .help("The stage LUT used for compensation instead of the default stage LUT")
+                        .required(false)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("stage-params")
+                        .long("stage-params")
+                        .help("A TOML or JSON file with the post processing parameters used for compensation")
+                        .required(false)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("resize")
+                .about("Resamples a LUT to a different number of nodes for each axis")
+                .arg(
+                    Arg::new("input")
+                        .index(1)
+                        .help(input_help())
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .index(2)
+                        .help(output_help())
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("size")
+                        .long("size")
+                        .help("The number of nodes for each axis of the output")
+                        .required(true)
+                        .takes_value(true)
+                        .value_parser(RangedU64ValueParser::<usize>::new().range(2..)),
+                )
+                .arg(
+                    Arg::new("linear-light")
+                        .long("linear-light")
+                        .help("Interpolates the sRGB values in linear light")
+                        .required(false)
+                        .takes_value(false),
+                ),
+        )
+        .subcommand(
+            Command::new("testpattern")
+                .about("Writes a diagnostic LUT with an obvious effect for checking the LUT in game")
+                .arg(
+                    Arg::new("output")
+                        .index(1)
+                        .help(output_help())
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("kind")
+                        .long("kind")
+                        .help("identity, grayscale ramp, inverted checkerboard, or hue rotation")
+                        .required(false)
+                        .takes_value(true)
+                        .possible_values(["identity", "ramp", "checker", "hue"])
+                        .default_value("hue"),
+                )
+                .arg(
+                    Arg::new("size")
+                        .long("size")
+                        .help("The number of nodes for each axis")
+                        .required(false)
+                        .takes_value(true)
+                        .value_parser(RangedU64ValueParser::<usize>::new().range(2..))
+                        .default_value("16"),
+                ),
+        )
+        .subcommand(
+            Command::new("check-swizzle")
+                .about("Checks if a .nutexb file deswizzles and swizzles back to the same data")
+                .arg(
+                    Arg::new("input")
+                        .index(1)
+                        .help("the input .nutexb file")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("params")
+                .about("Prints the post processing parameters used for stage LUT compensation")
+                .arg(
+                    Arg::new("stage-params")
+                        .long("stage-params")
+                        .help("A TOML or JSON file with custom post processing parameters to print instead of the defaults")
+                        .required(false)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("glsl")
+                        .long("glsl")
+                        .help("Print GLSL functions that apply the compensation with these parameters instead")
+                        .required(false)
+                        .takes_value(false),
+                ),
+        )
+        .subcommand(
+            Command::new("default-stage")
+                .about("Writes the default stage LUT used for stage LUT compensation")
+                .arg(
+                    Arg::new("output")
+                        .index(1)
+                        .help(output_help())
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+}
 
-    let input: PathBuf = matches.value_of("input").unwrap().into();
+fn input_arg() -> Arg<'static> {
+    let arg = Arg::new("input")
+        .index(1)
+        .help(input_help())
+        .takes_value(true);
+    if cfg!(feature = "zip") {
+        arg.required_unless_present("entry")
+    } else {
+        arg.required(true)
+    }
+}
 
-    let input_extension = input
-        .extension()
-        .unwrap()
-        .to_str()
-        .expect("The input file must have an extension.");
+fn zip_args() -> Vec<Arg<'static>> {
+    if cfg!(feature = "zip") {
+        vec![
+            Arg::new("zip")
+                .long("zip")
+                .help("Reads the input from an entry in a .zip archive instead")
+                .required(false)
+                .takes_value(true)
+                .requires("entry"),
+            Arg::new("entry")
+                .long("entry")
+                .help("The path of the input file in the --zip archive like mod/color_grading_lut.nutexb. The output is then the only positional argument")
+                .required(false)
+                .takes_value(true)
+                .requires("zip")
+                .conflicts_with("output"),
+        ]
+    } else {
+        Vec::new()
+    }
+}
+
+fn input_help() -> &'static str {
+    static HELP: OnceLock<String> = OnceLock::new();
+    formats_help(
+        &HELP,
+        "the input file",
+        smush_lut::format::supported_input_formats(),
+    )
+}
+
+fn inputs_help() -> &'static str {
+    static HELP: OnceLock<String> = OnceLock::new();
+    formats_help(
+        &HELP,
+        "the input files",
+        smush_lut::format::supported_input_formats(),
+    )
+}
+
+fn output_help() -> &'static str {
+    static HELP: OnceLock<String> = OnceLock::new();
+    formats_help(
+        &HELP,
+        "the output file",
+        smush_lut::format::supported_output_formats(),
+    )
+}
+
+// Clap requires static strings, so only format the help text once.
+fn formats_help(
+    help: &'static OnceLock<String>,
+    description: &str,
+    formats: &[&str],
+) -> &'static str {
+    help.get_or_init(|| format!("{} ({})", description, formats.join(", ")))
+}
+
+/// The results of converting a single input file.
+struct ConvertSummary {
+    input: PathBuf,
+    output: PathBuf,
+    size: usize,
+    parse_time: Duration,
+    export_time: Duration,
+}
+
+impl ConvertSummary {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "input": self.input,
+            "output": self.output,
+            "input_format": extension(&self.input),
+            "output_format": extension(&self.output),
+            "size": self.size,
+            "parse_time_ms": self.parse_time.as_secs_f64() * 1000.0,
+            "export_time_ms": self.export_time.as_secs_f64() * 1000.0,
+        })
+    }
+}
+
+fn convert(matches: &ArgMatches) {
+    let json = matches.is_present("json");
+    match convert_file(matches) {
+        Ok(summary) => {
+            if json {
+                println!("{}", summary.to_json());
+            } else {
+                eprintln!("Parse Time: {:?}", summary.parse_time);
+                eprintln!("Export Time: {:?}", summary.export_time);
+            }
+        }
+        Err(e) => {
+            if json {
+                println!("{}", serde_json::json!({ "error": e.to_string() }));
+            } else {
+                eprintln!("{}", e);
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+fn convert_file(matches: &ArgMatches) -> Result<ConvertSummary, Box<dyn Error>> {
+    let (input, output) = convert_paths(matches);
+    if input.extension().and_then(|e| e.to_str()).is_none() {
+        return Err("The input file must have an extension.".into());
+    }
 
     // Use the default conversion if no output is specified.
-    let output: PathBuf = match matches.value_of("output") {
-        Some(path) => path.into(),
-        None => match input_extension {
-            "nutexb" => input.with_extension("png").to_str().unwrap().into(),
-            _ => input.with_extension("nutexb").to_str().unwrap().into(),
-        },
-    };
+    let output = output.unwrap_or_else(|| default_output(matches, &input));
 
-    let lut_linear = parse_input(&input).unwrap();
+    let parse = Instant::now();
+    let mut lut_linear = parse_convert_input(matches, &input)
+        .map_err(|e| format!("Failed to read {:?}: {}", input, e))?;
+    if extension(&input).eq_ignore_ascii_case("cube") {
+        let color_space = cube_color_space(matches.value_of("cube-colorspace").unwrap());
+        lut_linear = lut_linear.convert_cube_color_space(color_space);
+    }
+    let parse_time = parse.elapsed();
+
+    if let Some(warning) = constant_warning(&lut_linear) {
+        eprintln!("{}", warning);
+    }
 
     // Check if the user wants to disable stage LUT compensation.
     let lut_final = if matches.is_present("raw") {
         lut_linear
     } else {
         // TODO: Make the stage lut an optional parameter?
-        let lut_stage = Lut3dLinear::default_stage();
+        let lut_stage = Lut3dLinear::default_stage_ref();
+
+        let mut params = match matches.value_of("stage-params") {
+            Some(path) => StageParams::from_file(path)
+                .map_err(|e| format!("Failed to read {:?}: {}", path, e))?,
+            None => StageParams::default(),
+        };
+        if matches.is_present("half-texel") {
+            params.half_texel_offset = true;
+        }
+        if matches.is_present("accurate") {
+            params.accurate = true;
+        }
+        let lut_final = correct_lut_with_params(&lut_linear, lut_stage, &params);
+        if matches.is_present("check-monotonic") {
+            if let Some(warning) = monotonic_warning(&lut_final) {
+                eprintln!("{}", warning);
+            }
+        }
+        lut_final
+    };
+
+    let export = Instant::now();
+    save_output_with_options(&lut_final, &output, &output_options(matches))
+        .map_err(|e| format!("Failed to write {:?}: {}", output, e))?;
+
+    if matches.is_present("also-cube") && extension(&output) != "cube" {
+        save_sibling_cube(&lut_final, &output)
+            .map_err(|e| format!("Failed to write .cube for {:?}: {}", output, e))?;
+    }
+    let export_time = export.elapsed();
+
+    Ok(ConvertSummary {
+        input,
+        output,
+        size: lut_final.size,
+        parse_time,
+        export_time,
+    })
+}
 
-        correct_lut(&lut_linear, &lut_stage)
+// Find the input and optional output paths.
+// The input is the --entry in the archive if present, so the output is the only positional argument.
+fn convert_paths(matches: &ArgMatches) -> (PathBuf, Option<PathBuf>) {
+    #[cfg(feature = "zip")]
+    if let Some(entry) = matches.value_of("entry") {
+        return (entry.into(), matches.value_of("input").map(PathBuf::from));
+    }
+
+    (
+        matches.value_of("input").unwrap().into(),
+        matches.value_of("output").map(PathBuf::from),
+    )
+}
+
+// The default output converts between .nutexb and .png next to the input file.
+#[cfg_attr(not(feature = "zip"), allow(unused_variables))]
+fn default_output(matches: &ArgMatches, input: &Path) -> PathBuf {
+    let extension = match extension(input) {
+        "nutexb" => "png",
+        _ => "nutexb",
+    };
+
+    #[cfg(feature = "zip")]
+    if let Some(zip) = matches.value_of("zip") {
+        // Entries can be in folders that don't exist outside the archive.
+        let name = input.file_name().unwrap_or_default();
+        return Path::new(zip)
+            .with_file_name(name)
+            .with_extension(extension);
+    }
+
+    input.with_extension(extension)
+}
+
+fn parse_convert_input(matches: &ArgMatches, input: &Path) -> Result<Lut3dLinear, Box<dyn Error>> {
+    let force_size = match matches.value_of("force-size") {
+        Some(size) => match size.parse() {
+            Ok(size) if size > 0 => Some(size),
+            _ => return Err("The force size must be a positive integer.".into()),
+        },
+        None => None,
     };
 
-    save_output(&lut_final, &output);
+    #[cfg(feature = "zip")]
+    if let (Some(zip), Some(entry)) = (matches.value_of("zip"), matches.value_of("entry")) {
+        let bytes = smush_lut::read_zip_entry(File::open(zip)?, entry)
+            .map_err(|e| format!("Could not read the entry from {:?}: {}", zip, e))?;
+        return parse_input_bytes(input, bytes, force_size);
+    }
+
+    parse_input_bytes(input, fs::read(input)?, force_size)
+}
+
+fn monotonic_warning(lut: &Lut3dLinear) -> Option<&'static str> {
+    if lut.is_monotonic() {
+        None
+    } else {
+        Some("Warning: The corrected LUT has values that decrease as the input increases. The edit may be too extreme to correct accurately and can cause artifacts in game.")
+    }
+}
+
+fn constant_warning(lut: &Lut3dLinear) -> Option<&'static str> {
+    if lut.is_constant() {
+        Some("Warning: The input LUT maps every color to the same color and will make the game render a single flat color. Check that the image isn't blank.")
+    } else {
+        None
+    }
+}
+
+fn cube_color_space(value: &str) -> CubeColorSpace {
+    match value {
+        "srgb" => CubeColorSpace::Srgb,
+        "linear" => CubeColorSpace::Linear,
+        _ => CubeColorSpace::Data,
+    }
 }
 
-fn parse_input(input: &Path) -> Option<Lut3dLinear> {
-    let parse = std::time::Instant::now();
-    let lut_linear: Option<Lut3dLinear> = match input.extension().unwrap().to_str().unwrap() {
-        "nutexb" => smush_lut::read_nutexb_lut(input).ok(),
-        "cube" => {
-            let contents = fs::read_to_string(input).unwrap();
-            let cube = smush_lut::CubeLut3d::from_text(&contents).unwrap();
+fn extension(path: &Path) -> &str {
+    path.extension().and_then(|e| e.to_str()).unwrap_or("")
+}
 
-            Some(cube.into())
+fn compare(matches: &ArgMatches) {
+    let a = Path::new(matches.value_of("a").unwrap());
+    let b = Path::new(matches.value_of("b").unwrap());
+
+    let diff = match compare_files(a, b) {
+        Ok(diff) => diff,
+        Err(e) => {
+            eprintln!("Failed to compare {:?} and {:?}: {}", a, b, e);
+            std::process::exit(1);
         }
-        _ => {
+    };
+
+    println!("Mean Difference: {}", diff.mean);
+    println!("Max Difference: {}", diff.max);
+    println!("RMSE: {}", diff.rmse);
+    println!("PSNR: {} dB", diff.psnr);
+
+    if let Some(&threshold) = matches.get_one::<f32>("threshold") {
+        if diff.max > threshold {
+            eprintln!(
+                "Max difference {} exceeds threshold {}",
+                diff.max, threshold
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+fn duplicates(matches: &ArgMatches) {
+    let inputs: Vec<_> = matches
+        .values_of("inputs")
+        .unwrap()
+        .map(Path::new)
+        .collect();
+    let groups = match duplicate_groups(&inputs) {
+        Ok(groups) => groups,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if groups.is_empty() {
+        println!("No duplicate LUTs found");
+    }
+    for (i, group) in groups.iter().enumerate() {
+        println!("Duplicate Group {}:", i + 1);
+        for path in group {
+            println!("    {:?}", path);
+        }
+    }
+}
+
+fn sequence(matches: &ArgMatches) {
+    let inputs: Vec<_> = matches
+        .values_of("inputs")
+        .unwrap()
+        .map(Path::new)
+        .collect();
+    let output = Path::new(matches.value_of("output").unwrap());
+
+    if let Err(e) = sequence_file(&inputs, output) {
+        eprintln!("Failed to write sequence {:?}: {}", output, e);
+        std::process::exit(1);
+    }
+}
+
+fn sequence_file(inputs: &[&Path], output: &Path) -> Result<(), Box<dyn Error>> {
+    let luts = inputs
+        .iter()
+        .map(|input| parse_input(input).map_err(|e| format!("Failed to read {:?}: {}", input, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut writer = BufWriter::new(File::create(output)?);
+    smush_lut::write_sequence(&luts, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Group the inputs with the same 8-bit values in the order they first appear.
+// Unique inputs aren't included in the result.
+fn duplicate_groups<'a>(inputs: &[&'a Path]) -> Result<Vec<Vec<&'a Path>>, Box<dyn Error>> {
+    let luts = inputs
+        .iter()
+        .map(|input| {
+            parse_input(input)
+                .map(|lut| (*input, lut))
+                .map_err(|e| format!("Failed to read {:?}: {}", input, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(group_by_content(&luts, Lut3dLinear::content_hash))
+}
+
+fn group_by_content<'a>(
+    luts: &[(&'a Path, Lut3dLinear)],
+    hash: impl Fn(&Lut3dLinear) -> u64,
+) -> Vec<Vec<&'a Path>> {
+    // The hash only finds candidates, so also compare the values in case of collisions.
+    let mut groups: Vec<(u64, usize, Vec<u8>, Vec<&Path>)> = Vec::new();
+    for (input, lut) in luts {
+        let hash = hash(lut);
+        let rgba = lut.to_rgba();
+        match groups.iter_mut().find(|(group_hash, size, group_rgba, _)| {
+            *group_hash == hash && *size == lut.size && *group_rgba == rgba
+        }) {
+            Some((_, _, _, group)) => group.push(input),
+            None => groups.push((hash, lut.size, rgba, vec![input])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(_, _, _, group)| group)
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+fn check_swizzle(matches: &ArgMatches) {
+    let input = Path::new(matches.value_of("input").unwrap());
+
+    match smush_lut::nutexb_swizzle_round_trips(input) {
+        Ok(true) => println!("{:?} uses the expected swizzle", input),
+        Ok(false) => {
+            eprintln!("{:?} does not use the expected swizzle", input);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Failed to check {:?}: {}", input, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn params(matches: &ArgMatches) {
+    match params_text(matches) {
+        Ok(text) => print!("{}", text),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn params_text(matches: &ArgMatches) -> Result<String, Box<dyn Error>> {
+    let params = match matches.value_of("stage-params") {
+        Some(path) => {
+            StageParams::from_file(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?
+        }
+        None => StageParams::default(),
+    };
+    if matches.is_present("glsl") {
+        Ok(correction_glsl(&params))
+    } else {
+        Ok(toml::to_string(&params)?)
+    }
+}
+
+fn posterize(matches: &ArgMatches) {
+    let input = Path::new(matches.value_of("input").unwrap());
+    let output = Path::new(matches.value_of("output").unwrap());
+    let levels = *matches.get_one::<usize>("levels").unwrap();
+
+    if let Err(e) = posterize_file(input, output, levels) {
+        eprintln!("Failed to posterize {:?}: {}", input, e);
+        std::process::exit(1);
+    }
+}
+
+fn posterize_file(input: &Path, output: &Path, levels: usize) -> Result<(), Box<dyn Error>> {
+    let lut = parse_input(input)?;
+    save_output(&lut.posterize(levels), output)
+}
+
+fn tone_curve(matches: &ArgMatches) {
+    let input = Path::new(matches.value_of("input").unwrap());
+    let output = Path::new(matches.value_of("output").unwrap());
+
+    if let Err(e) = tone_curve_file(input, output) {
+        eprintln!("Failed to write tone curve for {:?}: {}", input, e);
+        std::process::exit(1);
+    }
+}
+
+fn tone_curve_file(input: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+    let lut = parse_input(input)?;
+    let cube = CubeLut1d {
+        title: "".into(),
+        data: lut
+            .neutral_ramp()
+            .into_iter()
+            .map(|[r, g, b]| (r, g, b))
+            .collect(),
+    };
+    cube.write(&mut File::create(output)?)?;
+    Ok(())
+}
+
+fn export(matches: &ArgMatches) {
+    let (input, cube, preview) = export_paths(matches);
+    if let Err(e) = export_files(&input, &cube, &preview) {
+        eprintln!("Failed to export {:?}: {}", input, e);
+        std::process::exit(1);
+    }
+}
+
+// Find the input, .cube output, and preview output paths.
+fn export_paths(matches: &ArgMatches) -> (PathBuf, PathBuf, PathBuf) {
+    let input = PathBuf::from(matches.value_of("input").unwrap());
+    let folder = Path::new(matches.value_of("output").unwrap());
+    let cube = folder.join(matches.value_of("cube-name").unwrap());
+    let preview = folder.join(matches.value_of("preview-name").unwrap());
+    (input, cube, preview)
+}
+
+fn export_files(input: &Path, cube: &Path, preview: &Path) -> Result<(), Box<dyn Error>> {
+    // Export the values as is to match what the LUT looks like in game.
+    let lut = parse_input(input)?;
+    save_output(&lut, cube)?;
+    save_output(&lut, preview)
+}
+
+fn uncorrect(matches: &ArgMatches) {
+    let input = Path::new(matches.value_of("input").unwrap());
+    if let Err(e) = uncorrect_file(matches) {
+        eprintln!("Failed to uncorrect {:?}: {}", input, e);
+        std::process::exit(1);
+    }
+}
+
+fn uncorrect_file(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let input = Path::new(matches.value_of("input").unwrap());
+    let output = Path::new(matches.value_of("output").unwrap());
+
+    let lut_stage = match matches.value_of("stage") {
+        Some(path) => parse_input(Path::new(path))?,
+        None => Lut3dLinear::default_stage(),
+    };
+    let params = match matches.value_of("stage-params") {
+        Some(path) => {
+            StageParams::from_file(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?
+        }
+        None => StageParams::default(),
+    };
+
+    let lut_final = parse_input(input)?;
+    save_output(
+        &uncorrect_lut_with_params(&lut_final, &lut_stage, &params),
+        output,
+    )
+}
+
+fn resize(matches: &ArgMatches) {
+    let input = Path::new(matches.value_of("input").unwrap());
+    let output = Path::new(matches.value_of("output").unwrap());
+    let size = *matches.get_one::<usize>("size").unwrap();
+
+    let linear_light = matches.is_present("linear-light");
+    if let Err(e) = resize_file(input, output, size, linear_light) {
+        eprintln!("Failed to resize {:?}: {}", input, e);
+        std::process::exit(1);
+    }
+}
+
+fn resize_file(
+    input: &Path,
+    output: &Path,
+    size: usize,
+    linear_light: bool,
+) -> Result<(), Box<dyn Error>> {
+    if size < 2 {
+        return Err("The size must be at least 2.".into());
+    }
+
+    let lut = parse_input(input)?;
+    if size > lut.size {
+        eprintln!(
+            "Warning: Resizing from {} to {} won't add any detail to the LUT.",
+            lut.size, size
+        );
+    }
+    let resized = if linear_light {
+        lut.resample_linear_light(size, Interpolation::Trilinear)
+    } else {
+        lut.resample(size)
+    };
+    save_output(&resized, output)
+}
+
+fn test_pattern(matches: &ArgMatches) {
+    let output = Path::new(matches.value_of("output").unwrap());
+    let size = *matches.get_one::<usize>("size").unwrap();
+
+    let lut = pattern_from_matches(matches).create(size);
+    if let Err(e) = save_output(&lut, output) {
+        eprintln!("Failed to write {:?}: {}", output, e);
+        std::process::exit(1);
+    }
+}
+
+fn pattern_from_matches(matches: &ArgMatches) -> TestPattern {
+    match matches.value_of("kind").unwrap() {
+        "identity" => TestPattern::Identity,
+        "ramp" => TestPattern::Ramp,
+        "checker" => TestPattern::Checker,
+        _ => TestPattern::Hue,
+    }
+}
+
+fn compare_files(a: &Path, b: &Path) -> Result<LutDifference, Box<dyn Error>> {
+    let lut_a = parse_input(a)?;
+    let lut_b = parse_input(b)?;
+
+    // Resample the smaller LUT to avoid losing detail from the larger LUT.
+    let size = lut_a.size.max(lut_b.size);
+    let lut_a = lut_a.resample(size);
+    let lut_b = lut_b.resample(size);
+    Ok(smush_lut::difference(&lut_a, &lut_b).unwrap())
+}
+
+fn parse_input(input: &Path) -> Result<Lut3dLinear, Box<dyn Error>> {
+    parse_input_bytes(input, fs::read(input)?, None)
+}
+
+// Parses the contents of the file at path input like parse_input.
+// Image inputs use force_size instead of detecting the size from the dimensions if present.
+fn parse_input_bytes(
+    input: &Path,
+    bytes: Vec<u8>,
+    force_size: Option<usize>,
+) -> Result<Lut3dLinear, Box<dyn Error>> {
+    // Check the contents in case the file has the wrong extension.
+    let extension = input.extension().and_then(|e| e.to_str());
+    let format = match smush_lut::format::detect(extension, &bytes) {
+        // Prefer a detected image format over an extension that doesn't match the contents.
+        Some(format) if !format.sniff(&bytes) && image::guess_format(&bytes).is_ok() => None,
+        format => format,
+    };
+    let lut_linear = match format {
+        Some(format) => format.read(&mut Cursor::new(bytes))?,
+        #[cfg(feature = "openexr")]
+        None if extension.map(|e| e.eq_ignore_ascii_case("exr")) == Some(true) => {
+            smush_lut::read_exr_lut(input)?
+        }
+        None if extension == Some("bin") => {
+            // Raw unswizzled binaries don't store the size.
+            let size = smush_lut::infer_size_from_len(bytes.len()).ok_or_else(|| {
+                format!(
+                    "Unable to infer the LUT size from {} bytes of RGBA data.",
+                    bytes.len()
+                )
+            })?;
+            Lut3dLinear::from_rgba(size, bytes)
+        }
+        None => {
+            // PNG files written with --embed-source store the full precision values.
+            if image::guess_format(&bytes).ok() == Some(ImageFormat::Png) {
+                if let Some(lut) = smush_lut::read_png_source(Cursor::new(&bytes))? {
+                    return Ok(lut);
+                }
+            }
+
             // Assume anything else is some form of supported image format.
-            let img = image::open(input).unwrap().into_rgba8();
-            Lut3dLinear::try_from(&img).ok()
+            let mut reader = image::io::Reader::new(Cursor::new(bytes));
+            if let Ok(format) = ImageFormat::from_path(input) {
+                reader.set_format(format);
+            }
+            let img = reader
+                .with_guessed_format()?
+                .decode()
+                .map_err(|e| format!("Could not decode image {:?}: {}", input, e))?;
+            match force_size {
+                Some(size) => Lut3dLinear::from_dynamic_image_sized(&img, size)?,
+                None => Lut3dLinear::from_dynamic_image(&img)?,
+            }
         }
     };
 
-    eprintln!("Parse Time: {:?}", parse.elapsed());
+    Ok(lut_linear)
+}
 
-    lut_linear
+/// Settings that only apply to image outputs.
+struct OutputOptions {
+    /// The bits per channel for the image data.
+    bit_depth: u32,
+    /// Embed the full precision values in PNG outputs.
+    embed_source: bool,
+    /// The texture format for nutexb outputs.
+    nutexb_encoding: ColorEncoding,
 }
 
-fn save_output(lut_linear: &Lut3dLinear, output: &Path) {
-    let export = std::time::Instant::now();
-    match output.extension().unwrap().to_str().unwrap() {
-        "nutexb" => {
-            smush_lut::write_lut_to_nutexb(lut_linear, output).unwrap();
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            bit_depth: 8,
+            embed_source: false,
+            nutexb_encoding: ColorEncoding::Unorm,
         }
-        "cube" => {
-            smush_lut::linear_lut_to_cube(lut_linear, output).unwrap();
+    }
+}
+
+fn output_options(matches: &ArgMatches) -> OutputOptions {
+    OutputOptions {
+        bit_depth: matches.value_of("bit-depth").unwrap().parse().unwrap(),
+        embed_source: matches.is_present("embed-source"),
+        nutexb_encoding: match matches.value_of("nutexb-format") {
+            Some("srgb") => ColorEncoding::Srgb,
+            _ => ColorEncoding::Unorm,
+        },
+    }
+}
+
+fn save_output(lut_linear: &Lut3dLinear, output: &Path) -> Result<(), Box<dyn Error>> {
+    save_output_with_options(lut_linear, output, &OutputOptions::default())
+}
+
+fn save_output_with_options(
+    lut_linear: &Lut3dLinear,
+    output: &Path,
+    options: &OutputOptions,
+) -> Result<(), Box<dyn Error>> {
+    let extension = output
+        .extension()
+        .and_then(|e| e.to_str())
+        .ok_or_else(|| LutError::UnsupportedFormat(format!("{:?}", output)))?;
+
+    // Writing fails if the parent directory doesn't exist yet.
+    if let Some(parent) = output.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    match smush_lut::format::from_extension(extension) {
+        Some(_)
+            if extension.eq_ignore_ascii_case("nutexb")
+                && options.nutexb_encoding != ColorEncoding::Unorm =>
+        {
+            smush_lut::write_lut_to_nutexb_encoded(
+                lut_linear,
+                RoundingMode::Nearest,
+                options.nutexb_encoding,
+                output,
+            )?;
+        }
+        Some(format) => {
+            let mut file = BufWriter::new(File::create(output)?);
+            format.write(lut_linear, &mut file)?;
+            file.flush()?;
         }
-        "bin" => {
+        #[cfg(feature = "openexr")]
+        None if extension.eq_ignore_ascii_case("exr") => {
+            smush_lut::write_exr_lut(lut_linear, output)?;
+        }
+        None if extension == "bin" => {
             // Dump the unswizzled binary.
-            let mut file = File::create(output).unwrap();
-            file.write_all(&lut_linear.to_rgba()).unwrap();
+            let mut file = File::create(output)?;
+            file.write_all(&lut_linear.to_rgba())?;
+        }
+        None if options.embed_source && extension.eq_ignore_ascii_case("png") => {
+            let file = BufWriter::new(File::create(output)?);
+            smush_lut::write_png_with_source(lut_linear, file, options.bit_depth)?;
         }
-        _ => {
+        None if options.bit_depth == 16 => {
+            let img = ImageBuffer::<Rgba<u16>, Vec<u16>>::try_from(lut_linear)?;
+            img.save(output)?;
+        }
+        None => {
             // Assume anything else is some form of supported image format.
-            let img = image::RgbaImage::try_from(lut_linear).unwrap();
-            img.save(output).unwrap();
+            let img = image::RgbaImage::try_from(lut_linear)?;
+            img.save(output)?;
         }
     }
-    eprintln!("Export Time: {:?}", export.elapsed());
+    Ok(())
+}
+
+fn save_sibling_cube(lut_linear: &Lut3dLinear, output: &Path) -> Result<(), Box<dyn Error>> {
+    smush_lut::linear_lut_to_cube(lut_linear, output.with_extension("cube"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subcommand_inputs_list_supported_formats() {
+        for (name, arg) in [
+            ("compare", "a"),
+            ("compare", "b"),
+            ("duplicates", "inputs"),
+            ("sequence", "inputs"),
+            ("posterize", "input"),
+            ("tonecurve", "input"),
+            ("export", "input"),
+            ("uncorrect", "input"),
+            ("resize", "input"),
+        ] {
+            let command = cli();
+            let subcommand = command.find_subcommand(name).unwrap();
+            let help = subcommand
+                .get_arguments()
+                .find(|a| a.get_id() == arg)
+                .and_then(|a| a.get_help())
+                .unwrap();
+            for format in smush_lut::format::supported_input_formats() {
+                assert!(help.contains(format), "{} {} {}", name, arg, format);
+            }
+        }
+    }
+
+    #[test]
+    fn save_output_nested_missing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("out").join("subdir").join("lut.cube");
+
+        save_output(&Lut3dLinear::identity(), &output).unwrap();
+        assert!(output.exists());
+    }
+
+    #[test]
+    fn save_output_no_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("out").join("lut");
+
+        let error = save_output(&Lut3dLinear::identity(), &output).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<LutError>(),
+            Some(LutError::UnsupportedFormat(_))
+        ));
+        assert!(!dir.path().join("out").exists());
+    }
+
+    #[test]
+    fn parse_input_bin_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("lut.bin");
+
+        let lut = Lut3dLinear::default_stage().resample(8);
+        save_output(&lut, &output).unwrap();
+        assert_eq!(lut.to_rgba(), parse_input(&output).unwrap().to_rgba());
+    }
+
+    #[test]
+    fn parse_input_bin_invalid_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.bin");
+        fs::write(&input, [0u8; 10]).unwrap();
+
+        assert_eq!(
+            "Unable to infer the LUT size from 10 bytes of RGBA data.",
+            parse_input(&input).unwrap_err().to_string()
+        );
+    }
+
+    #[cfg(feature = "openexr")]
+    #[test]
+    fn save_output_exr_extended_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("lut.exr");
+
+        let lut = Lut3dLinear::from_fn(8, |[r, g, b]| [r * 1.5, g, b]);
+        save_output(&lut, &output).unwrap();
+        assert_eq!(lut, parse_input(&output).unwrap());
+    }
+
+    #[test]
+    fn parse_input_text_file_as_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.png");
+        fs::write(&input, "not an image").unwrap();
+
+        let error = parse_input(&input).unwrap_err().to_string();
+        assert!(
+            error.starts_with(&format!("Could not decode image {:?}: ", input)),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn compare_threshold_args() {
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            "compare",
+            "a.cube",
+            "b.cube",
+            "--threshold",
+            "0.01",
+        ]);
+        let sub_matches = matches.subcommand_matches("compare").unwrap();
+        assert_eq!(Some(&0.01), sub_matches.get_one::<f32>("threshold"));
+
+        assert!(cli()
+            .try_get_matches_from([
+                "smush_lut",
+                "compare",
+                "a.cube",
+                "b.cube",
+                "--threshold",
+                "small"
+            ])
+            .is_err());
+    }
+
+    #[test]
+    fn compare_nutexb_cube() {
+        let dir = tempfile::tempdir().unwrap();
+        let nutexb = dir.path().join("stage.nutexb");
+        let cube = dir.path().join("look.cube");
+
+        let lut = Lut3dLinear::default_stage();
+        save_output(&lut, &nutexb).unwrap();
+        save_output(&lut.resample(33), &cube).unwrap();
+
+        let diff = compare_files(&nutexb, &cube).unwrap();
+        assert!(diff.max < 1.0 / 255.0);
+    }
+
+    #[test]
+    fn default_stage_cube() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("default_stage.cube");
+
+        save_output(&Lut3dLinear::default_stage(), &output).unwrap();
+        assert_eq!(Lut3dLinear::default_stage(), parse_input(&output).unwrap());
+    }
+
+    #[test]
+    fn save_output_also_cube() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("lut.nutexb");
+
+        let lut = Lut3dLinear::default_stage();
+        save_output(&lut, &output).unwrap();
+        save_sibling_cube(&lut, &output).unwrap();
+
+        let nutexb = parse_input(&output).unwrap();
+        let cube = parse_input(&dir.path().join("lut.cube")).unwrap();
+        assert_eq!(lut, cube);
+        assert_eq!(nutexb.to_rgba(), cube.to_rgba());
+    }
+
+    #[test]
+    fn convert_json_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.cube");
+        let output = dir.path().join("lut.nutexb");
+        save_output(&Lut3dLinear::identity(), &input).unwrap();
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "--json",
+        ]);
+        let summary = convert_file(&matches).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&summary.to_json().to_string()).unwrap();
+        assert_eq!(input.to_str().unwrap(), json["input"]);
+        assert_eq!(output.to_str().unwrap(), json["output"]);
+        assert_eq!("cube", json["input_format"]);
+        assert_eq!("nutexb", json["output_format"]);
+        assert_eq!(16, json["size"]);
+        assert!(json["parse_time_ms"].is_f64());
+        assert!(json["export_time_ms"].is_f64());
+    }
+
+    #[test]
+    fn params_default_values() {
+        let matches = cli().get_matches_from(["smush_lut", "params"]);
+        let text = params_text(matches.subcommand_matches("params").unwrap()).unwrap();
+        for line in [
+            "texcoord_scale = 0.9375",
+            "texcoord_offset = 0.03125",
+            "compression = 0.99961",
+            "gain = 1.3703",
+            "gamma = 2.2",
+            "half_texel_offset = false",
+        ] {
+            assert!(text.contains(line), "{}", text);
+        }
+    }
+
+    #[test]
+    fn params_stage_params_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("params.json");
+        std::fs::write(&path, r#"{"gamma": 2.4}"#).unwrap();
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            "params",
+            "--stage-params",
+            path.to_str().unwrap(),
+        ]);
+        let text = params_text(matches.subcommand_matches("params").unwrap()).unwrap();
+        assert!(text.contains("gamma = 2.4"));
+        assert!(text.contains("gain = 1.3703"));
+    }
+
+    #[test]
+    fn params_glsl() {
+        let matches = cli().get_matches_from(["smush_lut", "params", "--glsl"]);
+        let text = params_text(matches.subcommand_matches("params").unwrap()).unwrap();
+        assert!(text.contains("const float GAMMA = 2.2;"));
+        assert!(text.contains("vec3 edit_to_final(vec3 edit_color, vec3 texcoord) {"));
+    }
+
+    #[test]
+    fn convert_package_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.cube");
+        let output = dir.path().join("lut.nutexb");
+        let expected = dir.path().join("expected.nutexb");
+
+        let lut = Lut3dLinear::from_matrix(
+            16,
+            [[0.8, 0.15, 0.03], [0.1, 0.75, 0.1], [0.05, 0.05, 0.85]],
+            [0.011, 0.007, 0.003],
+        );
+        save_output(&lut, &input).unwrap();
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "--package-only",
+        ]);
+        assert!(matches.is_present("raw"));
+        convert_file(&matches).unwrap();
+
+        // The values should be packaged without any correction.
+        save_output(&parse_input(&input).unwrap(), &expected).unwrap();
+        assert_eq!(fs::read(expected).unwrap(), fs::read(output).unwrap());
+    }
+
+    #[test]
+    fn convert_cube_colorspace_srgb() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.cube");
+        let output = dir.path().join("out.cube");
+
+        let lut = Lut3dLinear::default_stage();
+        save_output(&lut, &input).unwrap();
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "--raw",
+            "--cube-colorspace",
+            "srgb",
+        ]);
+        convert_file(&matches).unwrap();
+
+        assert_eq!(
+            lut.convert_cube_color_space(CubeColorSpace::Srgb),
+            parse_input(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn convert_missing_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("missing.cube");
+
+        let matches = cli().get_matches_from(["smush_lut", input.to_str().unwrap(), "--json"]);
+        let error = convert_file(&matches).err().unwrap().to_string();
+        assert!(error.starts_with("Failed to read"));
+    }
+
+    #[test]
+    fn convert_stage_params_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.cube");
+        let output = dir.path().join("lut_out.cube");
+        let params = dir.path().join("params.toml");
+        save_output(&Lut3dLinear::identity(), &input).unwrap();
+
+        let stage_params = StageParams {
+            gamma: 2.4,
+            ..Default::default()
+        };
+        stage_params.write_to_file(&params).unwrap();
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "--stage-params",
+            params.to_str().unwrap(),
+        ]);
+        convert_file(&matches).unwrap();
+
+        let expected = correct_lut_with_params(
+            &Lut3dLinear::identity(),
+            &Lut3dLinear::default_stage(),
+            &stage_params,
+        );
+        assert_eq!(expected.to_rgba(), parse_input(&output).unwrap().to_rgba());
+    }
+
+    #[test]
+    fn save_output_16_bit_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let output8 = dir.path().join("lut8.png");
+        let output16 = dir.path().join("lut16.png");
+
+        // Use values that aren't exactly representable with 8 bits.
+        let lut = Lut3dLinear::from_matrix(
+            16,
+            [[0.8, 0.15, 0.03], [0.1, 0.75, 0.1], [0.05, 0.05, 0.85]],
+            [0.011, 0.007, 0.003],
+        );
+        save_output(&lut, &output8).unwrap();
+        let options = OutputOptions {
+            bit_depth: 16,
+            ..Default::default()
+        };
+        save_output_with_options(&lut, &output16, &options).unwrap();
+
+        let diff8 = smush_lut::difference(&lut, &parse_input(&output8).unwrap()).unwrap();
+        let diff16 = smush_lut::difference(&lut, &parse_input(&output16).unwrap()).unwrap();
+        assert!(diff16.max < diff8.max);
+        assert!(diff16.max < 1.0 / 65535.0);
+    }
+
+    #[test]
+    fn posterize_invalid_levels() {
+        for levels in ["0", "1", "-4", "many"] {
+            assert!(cli()
+                .try_get_matches_from([
+                    "smush_lut",
+                    "posterize",
+                    "in.cube",
+                    "out.cube",
+                    "--levels",
+                    levels
+                ])
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn posterize_cube() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.cube");
+        let output = dir.path().join("posterized.cube");
+        save_output(&Lut3dLinear::default_stage(), &input).unwrap();
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            "posterize",
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "--levels",
+            "4",
+        ]);
+        let sub_matches = matches.subcommand_matches("posterize").unwrap();
+        assert_eq!(Some(&4), sub_matches.get_one::<usize>("levels"));
+
+        posterize_file(&input, &output, 4).unwrap();
+        assert_eq!(
+            Lut3dLinear::default_stage().posterize(4).to_rgba(),
+            parse_input(&output).unwrap().to_rgba()
+        );
+    }
+
+    #[test]
+    fn parse_input_wrong_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let lut = Lut3dLinear::default_stage();
+
+        let cube = dir.path().join("lut.cube");
+        let nutexb = dir.path().join("lut.nutexb");
+        let png = dir.path().join("lut.png");
+        save_output(&lut, &cube).unwrap();
+        save_output(&lut, &nutexb).unwrap();
+        save_output(&lut, &png).unwrap();
+
+        for (path, renamed) in [
+            (cube, "cube.txt"),
+            (nutexb, "nutexb.png"),
+            (png, "png.cube"),
+        ] {
+            let renamed = dir.path().join(renamed);
+            fs::rename(path, &renamed).unwrap();
+            assert_eq!(lut.to_rgba(), parse_input(&renamed).unwrap().to_rgba());
+        }
+    }
+
+    #[test]
+    fn test_pattern_kinds() {
+        for (kind, pattern) in [
+            ("identity", TestPattern::Identity),
+            ("ramp", TestPattern::Ramp),
+            ("checker", TestPattern::Checker),
+            ("hue", TestPattern::Hue),
+        ] {
+            let matches =
+                cli().get_matches_from(["smush_lut", "testpattern", "--kind", kind, "out.png"]);
+            let sub_matches = matches.subcommand_matches("testpattern").unwrap();
+            assert_eq!(pattern, pattern_from_matches(sub_matches));
+        }
+    }
+
+    #[test]
+    fn test_pattern_size_args() {
+        let matches = cli().get_matches_from(["smush_lut", "testpattern", "out.png"]);
+        let sub_matches = matches.subcommand_matches("testpattern").unwrap();
+        assert_eq!(Some(&16), sub_matches.get_one::<usize>("size"));
+
+        for size in ["0", "1", "large"] {
+            assert!(cli()
+                .try_get_matches_from(["smush_lut", "testpattern", "--size", size, "out.png"])
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn save_output_embed_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("lut.png");
+
+        let lut = Lut3dLinear::from_matrix(
+            16,
+            [[0.8, 0.15, 0.03], [0.1, 0.75, 0.1], [0.05, 0.05, 0.85]],
+            [0.011, 0.007, 0.003],
+        );
+        let options = OutputOptions {
+            embed_source: true,
+            ..Default::default()
+        };
+        save_output_with_options(&lut, &output, &options).unwrap();
+
+        assert_eq!(lut, parse_input(&output).unwrap());
+    }
+
+    #[test]
+    fn save_output_nutexb_srgb() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("lut.nutexb");
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            "lut.cube",
+            output.to_str().unwrap(),
+            "--nutexb-format",
+            "srgb",
+        ]);
+        let options = output_options(&matches);
+        assert_eq!(ColorEncoding::Srgb, options.nutexb_encoding);
+
+        let lut = Lut3dLinear::default_stage();
+        save_output_with_options(&lut, &output, &options).unwrap();
+
+        let nutexb = nutexb::NutexbFile::read_from_file(&output).unwrap();
+        assert_eq!(
+            nutexb::NutexbFormat::R8G8B8A8Srgb,
+            nutexb.footer.image_format
+        );
+        approx::assert_relative_eq!(
+            lut.data[..],
+            parse_input(&output).unwrap().data[..],
+            epsilon = 0.005
+        );
+    }
+
+    #[test]
+    fn save_output_nutexb_unorm_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("lut.nutexb");
+
+        let matches = cli().get_matches_from(["smush_lut", "lut.cube", output.to_str().unwrap()]);
+        save_output_with_options(&Lut3dLinear::identity(), &output, &output_options(&matches))
+            .unwrap();
+
+        let nutexb = nutexb::NutexbFile::read_from_file(&output).unwrap();
+        assert_eq!(
+            nutexb::NutexbFormat::R8G8B8A8Unorm,
+            nutexb.footer.image_format
+        );
+    }
+
+    #[test]
+    fn resize_cube_up_and_down() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut2.cube");
+        let resized = dir.path().join("lut33.cube");
+        let output = dir.path().join("lut2_out.cube");
+
+        // Trilinear interpolation of a size 2 LUT is exact at the new nodes.
+        let lut = Lut3dLinear::from_matrix(
+            2,
+            [[0.8, 0.15, 0.03], [0.1, 0.75, 0.1], [0.05, 0.05, 0.85]],
+            [0.011, 0.007, 0.003],
+        );
+        save_output(&lut, &input).unwrap();
+
+        resize_file(&input, &resized, 33, false).unwrap();
+        assert_eq!(33, parse_input(&resized).unwrap().size);
+
+        resize_file(&resized, &output, 2, false).unwrap();
+        let new_lut = parse_input(&output).unwrap();
+        assert_eq!(2, new_lut.size);
+        approx::assert_relative_eq!(lut.data[..], new_lut.data[..], epsilon = 1e-5);
+    }
+
+    #[test]
+    fn resize_invalid_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.cube");
+        let output = dir.path().join("out.cube");
+        save_output(&Lut3dLinear::identity(), &input).unwrap();
+
+        assert!(resize_file(&input, &output, 1, false).is_err());
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn resize_size_args() {
+        let matches =
+            cli().get_matches_from(["smush_lut", "resize", "in.cube", "out.cube", "--size", "33"]);
+        let sub_matches = matches.subcommand_matches("resize").unwrap();
+        assert_eq!(Some(&33), sub_matches.get_one::<usize>("size"));
+
+        for size in ["1", "-4", "large"] {
+            assert!(cli()
+                .try_get_matches_from([
+                    "smush_lut",
+                    "resize",
+                    "in.cube",
+                    "out.cube",
+                    "--size",
+                    size
+                ])
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn constant_warning_blank_image() {
+        assert!(constant_warning(&Lut3dLinear::empty_rgba(16)).is_some());
+        assert_eq!(None, constant_warning(&Lut3dLinear::identity()));
+    }
+
+    #[test]
+    fn monotonic_warning_extreme_edit() {
+        let lut_stage = Lut3dLinear::default_stage();
+        let params = StageParams::default();
+
+        let edit = Lut3dLinear::identity();
+        let corrected = correct_lut_with_params(&edit, &lut_stage, &params);
+        assert_eq!(None, monotonic_warning(&corrected));
+
+        // Inverting colors reverses every channel.
+        let edit = Lut3dLinear::from_matrix(
+            16,
+            [[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]],
+            [1.0; 3],
+        );
+        let corrected = correct_lut_with_params(&edit, &lut_stage, &params);
+        assert!(monotonic_warning(&corrected).is_some());
+    }
+
+    #[test]
+    fn tone_curve_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.cube");
+        let output = dir.path().join("curve.cube");
+        save_output(&Lut3dLinear::identity(), &input).unwrap();
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            "tonecurve",
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+        ]);
+        assert!(matches.subcommand_matches("tonecurve").is_some());
+
+        tone_curve_file(&input, &output).unwrap();
+        let text = std::fs::read_to_string(&output).unwrap();
+        assert!(text.contains("LUT_1D_SIZE 16"));
+        assert!(!text.contains("LUT_3D_SIZE"));
+
+        let ramp: Vec<Vec<f32>> = text
+            .lines()
+            .filter(|l| l.starts_with(|c: char| c.is_ascii_digit()))
+            .map(|l| l.split_whitespace().map(|f| f.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(16, ramp.len());
+        for (i, rgb) in ramp.iter().enumerate() {
+            let expected = i as f32 / 15.0;
+            for value in rgb {
+                assert!((value - expected).abs() < 0.5 / 255.0, "{:?}", ramp);
+            }
+        }
+    }
+
+    #[test]
+    fn export_cube_and_preview() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.nutexb");
+        save_output(&Lut3dLinear::default_stage(), &input).unwrap();
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            "export",
+            input.to_str().unwrap(),
+            dir.path().join("out").to_str().unwrap(),
+            "--preview-name",
+            "strip.png",
+        ]);
+        let sub_matches = matches.subcommand_matches("export").unwrap();
+        let (input_path, cube, preview) = export_paths(sub_matches);
+        export_files(&input_path, &cube, &preview).unwrap();
+
+        assert_eq!(input, input_path);
+        assert_eq!(dir.path().join("out").join("lut.cube"), cube);
+        assert_eq!(dir.path().join("out").join("strip.png"), preview);
+
+        let cube = parse_input(&cube).unwrap();
+        let preview = parse_input(&preview).unwrap();
+        assert_eq!(Lut3dLinear::default_stage(), cube);
+        assert_eq!(cube.to_rgba(), preview.to_rgba());
+    }
+
+    #[test]
+    fn uncorrect_corrected_cube() {
+        let dir = tempfile::tempdir().unwrap();
+        let stage = dir.path().join("stage.cube");
+        let input = dir.path().join("game_lut.cube");
+        let output = dir.path().join("neutral.cube");
+
+        let lut_stage = Lut3dLinear::default_stage();
+        let lut_edit = Lut3dLinear::from_matrix(
+            16,
+            [[0.9, 0.1, 0.0], [0.0, 0.9, 0.1], [0.1, 0.0, 0.9]],
+            [0.0; 3],
+        );
+        save_output(&lut_stage, &stage).unwrap();
+        let lut_final = correct_lut_with_params(&lut_edit, &lut_stage, &StageParams::default());
+        save_output(&lut_final, &input).unwrap();
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            "uncorrect",
+            "--stage",
+            stage.to_str().unwrap(),
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+        ]);
+        uncorrect_file(matches.subcommand_matches("uncorrect").unwrap()).unwrap();
+
+        let uncorrected = parse_input(&output).unwrap();
+        let difference = smush_lut::difference(&uncorrected, &lut_edit).unwrap();
+        assert!(difference.max < 0.0001);
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn convert_zip_entry() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let nutexb = dir.path().join("lut.nutexb");
+        let archive = dir.path().join("mod.zip");
+        let output = dir.path().join("out.cube");
+
+        let lut = Lut3dLinear::default_stage();
+        save_output(&lut, &nutexb).unwrap();
+
+        let mut writer = zip::ZipWriter::new(File::create(&archive).unwrap());
+        writer
+            .start_file("mod/color_grading_lut.nutexb", Default::default())
+            .unwrap();
+        writer.write_all(&fs::read(&nutexb).unwrap()).unwrap();
+        writer.finish().unwrap();
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            "--zip",
+            archive.to_str().unwrap(),
+            "--entry",
+            "mod/color_grading_lut.nutexb",
+            output.to_str().unwrap(),
+            "--raw",
+        ]);
+        let summary = convert_file(&matches).unwrap();
+        assert_eq!(Path::new("mod/color_grading_lut.nutexb"), summary.input);
+        assert_eq!(output, summary.output);
+        assert_eq!(lut.to_rgba(), parse_input(&output).unwrap().to_rgba());
+
+        // The default output is next to the archive instead of in the entry's folder.
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            "--zip",
+            archive.to_str().unwrap(),
+            "--entry",
+            "mod/color_grading_lut.nutexb",
+            "--raw",
+        ]);
+        let summary = convert_file(&matches).unwrap();
+        assert_eq!(dir.path().join("color_grading_lut.png"), summary.output);
+        assert_eq!(
+            lut.to_rgba(),
+            parse_input(&summary.output).unwrap().to_rgba()
+        );
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn zip_entry_args() {
+        // --zip and --entry are only valid together.
+        let args = [
+            vec!["smush_lut", "--zip", "mod.zip", "out.cube"],
+            vec!["smush_lut", "--entry", "lut.nutexb", "out.cube"],
+            vec![
+                "smush_lut",
+                "--zip",
+                "mod.zip",
+                "--entry",
+                "lut.nutexb",
+                "a.cube",
+                "b.cube",
+            ],
+        ];
+        for args in args {
+            assert!(cli().try_get_matches_from(args).is_err());
+        }
+
+        // The positional arguments keep their meaning without --zip.
+        let matches = cli().get_matches_from(["smush_lut", "lut.nutexb", "out.cube"]);
+        assert_eq!(
+            (PathBuf::from("lut.nutexb"), Some(PathBuf::from("out.cube"))),
+            convert_paths(&matches)
+        );
+    }
+
+    #[test]
+    fn duplicate_groups_one_duplicate() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.cube");
+        let b = dir.path().join("b.nutexb");
+        let c = dir.path().join("c.cube");
+        let d = dir.path().join("d.png");
+
+        // Different formats with the same 8-bit values are still duplicates.
+        save_output(&Lut3dLinear::default_stage(), &a).unwrap();
+        save_output(&Lut3dLinear::identity(), &b).unwrap();
+        save_output(&Lut3dLinear::default_stage().rotate_hue(30.0), &c).unwrap();
+        save_output(&Lut3dLinear::default_stage(), &d).unwrap();
+
+        let inputs = [a.as_path(), b.as_path(), c.as_path(), d.as_path()];
+        assert_eq!(
+            vec![vec![a.as_path(), d.as_path()]],
+            duplicate_groups(&inputs).unwrap()
+        );
+    }
+
+    #[test]
+    fn group_by_content_hash_collision() {
+        let (a, b, c) = (
+            Path::new("a.cube"),
+            Path::new("b.cube"),
+            Path::new("c.cube"),
+        );
+        let luts = [
+            (a, Lut3dLinear::default_stage()),
+            (b, Lut3dLinear::identity()),
+            (c, Lut3dLinear::default_stage()),
+        ];
+
+        // Every LUT has the same hash, but only equal LUTs are duplicates.
+        assert_eq!(vec![vec![a, c]], group_by_content(&luts, |_| 0));
+    }
+
+    #[test]
+    fn sequence_two_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.cube");
+        let b = dir.path().join("b.nutexb");
+        let output = dir.path().join("out.seq");
+        save_output(&Lut3dLinear::default_stage(), &a).unwrap();
+        save_output(&Lut3dLinear::identity(), &b).unwrap();
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            "sequence",
+            a.to_str().unwrap(),
+            b.to_str().unwrap(),
+            output.to_str().unwrap(),
+        ]);
+        let sub_matches = matches.subcommand_matches("sequence").unwrap();
+        assert_eq!(2, sub_matches.values_of("inputs").unwrap().count());
+        assert_eq!(
+            Some(output.to_str().unwrap()),
+            sub_matches.value_of("output")
+        );
+
+        sequence(sub_matches);
+        let luts = smush_lut::read_sequence(&mut File::open(&output).unwrap()).unwrap();
+        assert_eq!(2, luts.len());
+        assert_eq!(parse_input(&a).unwrap(), luts[0]);
+        assert_eq!(parse_input(&b).unwrap(), luts[1]);
+    }
+
+    #[test]
+    fn duplicate_groups_no_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.cube");
+        let b = dir.path().join("b.cube");
+        save_output(&Lut3dLinear::default_stage(), &a).unwrap();
+        save_output(&Lut3dLinear::identity(), &b).unwrap();
+
+        assert!(duplicate_groups(&[a.as_path(), b.as_path()])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn convert_force_size_square_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.png");
+        let output = dir.path().join("lut.cube");
+
+        // A square image isn't a valid LUT layout without knowing the size.
+        let lut = Lut3dLinear::default_stage().resample(8);
+        let mut img = image::RgbaImage::new(64, 64);
+        image::imageops::replace(&mut img, &image::RgbaImage::try_from(&lut).unwrap(), 0, 0);
+        img.save(&input).unwrap();
+        assert!(parse_input(&input).is_err());
+
+        let matches = cli().get_matches_from([
+            "smush_lut",
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "--raw",
+            "--force-size",
+            "8",
+        ]);
+        convert_file(&matches).unwrap();
+        assert_eq!(lut.to_rgba(), parse_input(&output).unwrap().to_rgba());
+    }
+
+    #[test]
+    fn convert_force_size_rgba16_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.png");
+
+        // Forcing the size should keep the 16-bit precision of the image.
+        let lut = Lut3dLinear::default_stage().resample(8);
+        let strip: ImageBuffer<Rgba<u16>, _> =
+            ImageBuffer::from_raw(64, 8, lut.to_rgba16()).unwrap();
+        let mut img = ImageBuffer::<Rgba<u16>, Vec<u16>>::new(64, 64);
+        image::imageops::replace(&mut img, &strip, 0, 0);
+        img.save(&input).unwrap();
+
+        let bytes = std::fs::read(&input).unwrap();
+        let parsed = parse_input_bytes(&input, bytes, Some(8)).unwrap();
+        assert_eq!(lut.to_rgba16(), parsed.to_rgba16());
+    }
+
+    #[test]
+    fn convert_force_size_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("lut.cube");
+        save_output(&Lut3dLinear::default_stage(), &input).unwrap();
+
+        let matches =
+            cli().get_matches_from(["smush_lut", input.to_str().unwrap(), "--force-size", "0"]);
+        let error = convert_file(&matches).err().unwrap().to_string();
+        assert!(
+            error.ends_with("The force size must be a positive integer."),
+            "{}",
+            error
+        );
+    }
 }
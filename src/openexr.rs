@@ -0,0 +1,87 @@
+use std::path::Path;
+
+use exr::prelude::{read_first_rgba_layer_from_file, write_rgba_file};
+
+use crate::{Lut3dLinear, LutError};
+
+/// Reads a LUT from a floating point EXR image with the same layout as the [RgbaImage](image::RgbaImage) conversion.
+/// Values outside the range `0.0` to `1.0` are preserved.
+pub fn read_exr_lut<P: AsRef<Path>>(path: P) -> Result<Lut3dLinear, LutError> {
+    let image = read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| {
+            let (width, height) = (resolution.width(), resolution.height());
+            (width, height, vec![0.0f32; width * height * 4])
+        },
+        |(width, _, data), position, (r, g, b, a): (f32, f32, f32, f32)| {
+            let i = (position.y() * *width + position.x()) * 4;
+            data[i..i + 4].copy_from_slice(&[r, g, b, a]);
+        },
+    )
+    .map_err(exr_error)?;
+
+    let (width, height, data) = image.layer_data.channel_data.pixels;
+    if width != height * height {
+        return Err(LutError::InvalidDimensions(
+            "Invalid dimensions. Expected width to equal height * height.",
+        ));
+    }
+
+    Ok(Lut3dLinear { size: height, data })
+}
+
+/// Writes `lut` as a 32-bit floating point EXR image with the same layout as the [RgbaImage](image::RgbaImage) conversion.
+pub fn write_exr_lut<P: AsRef<Path>>(lut: &Lut3dLinear, path: P) -> Result<(), LutError> {
+    let width = lut.size * lut.size;
+    write_rgba_file(path, width, lut.size, |x, y| {
+        let i = (y * width + x) * 4;
+        (
+            lut.data[i],
+            lut.data[i + 1],
+            lut.data[i + 2],
+            lut.data[i + 3],
+        )
+    })
+    .map_err(exr_error)
+}
+
+fn exr_error(e: exr::error::Error) -> LutError {
+    match e {
+        exr::error::Error::Io(e) => LutError::Io(e),
+        e => LutError::Parse(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exr_round_trip_extended_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lut.exr");
+
+        let mut lut = Lut3dLinear::from_matrix(
+            8,
+            [[0.8, 0.15, 0.03], [0.1, 0.75, 0.1], [0.05, 0.05, 0.85]],
+            [0.011, 0.007, 0.003],
+        );
+        let last = lut.data.len() - 4;
+        lut.data[last] = 1.5;
+
+        write_exr_lut(&lut, &path).unwrap();
+        assert_eq!(lut, read_exr_lut(&path).unwrap());
+    }
+
+    #[test]
+    fn read_exr_invalid_dimensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lut.exr");
+        write_rgba_file(&path, 8, 8, |_, _| (0.0f32, 0.0f32, 0.0f32, 1.0f32)).unwrap();
+
+        assert!(matches!(
+            read_exr_lut(&path),
+            Err(LutError::InvalidDimensions(_))
+        ));
+    }
+}
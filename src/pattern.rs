@@ -0,0 +1,87 @@
+use crate::{create_identity_lut_f32, index3d, Lut3dLinear};
+
+/// Diagnostic LUTs with obvious effects for checking that a LUT is applied in game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestPattern {
+    /// Each node maps its RGB coordinate to itself.
+    Identity,
+    /// Each node maps to the grayscale luminance of its coordinate using Rec. 709 weights.
+    Ramp,
+    /// Nodes alternate between the identity and the inverted color `1.0 - c`
+    /// like a 3D checkerboard. The node at the origin uses the identity.
+    Checker,
+    /// Rotates hues by 120 degrees by mapping `(r, g, b)` to `(g, b, r)`.
+    Hue,
+}
+
+impl TestPattern {
+    /// Creates the pattern with the given `size` for each axis.
+    /// Alpha is always `1.0`.
+    pub fn create(&self, size: usize) -> Lut3dLinear {
+        let mut data = create_identity_lut_f32(size);
+        for z in 0..size {
+            for y in 0..size {
+                for x in 0..size {
+                    let i = index3d(x, y, z, size, size) * 4;
+                    let [r, g, b] = [data[i], data[i + 1], data[i + 2]];
+                    let rgb = match self {
+                        TestPattern::Identity => [r, g, b],
+                        TestPattern::Ramp => {
+                            let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+                            [luma; 3]
+                        }
+                        TestPattern::Checker if (x + y + z) % 2 == 1 => [1.0 - r, 1.0 - g, 1.0 - b],
+                        TestPattern::Checker => [r, g, b],
+                        TestPattern::Hue => [g, b, r],
+                    };
+                    data[i..i + 3].copy_from_slice(&rgb);
+                }
+            }
+        }
+
+        Lut3dLinear { size, data }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(lut: &Lut3dLinear, x: usize, y: usize, z: usize) -> [f32; 3] {
+        let i = index3d(x, y, z, lut.size, lut.size) * 4;
+        [lut.data[i], lut.data[i + 1], lut.data[i + 2]]
+    }
+
+    #[test]
+    fn identity_pattern() {
+        let lut = TestPattern::Identity.create(16);
+        assert!(lut.is_identity());
+        assert_eq!([1.0 / 15.0, 0.0, 1.0], rgb(&lut, 1, 0, 15));
+    }
+
+    #[test]
+    fn ramp_pattern() {
+        let lut = TestPattern::Ramp.create(3);
+        assert_eq!([0.0; 3], rgb(&lut, 0, 0, 0));
+        assert_eq!([0.2126; 3], rgb(&lut, 2, 0, 0));
+        assert_eq!([0.7152 * 0.5; 3], rgb(&lut, 0, 1, 0));
+        approx::assert_relative_eq!(&[1.0; 3][..], &rgb(&lut, 2, 2, 2)[..], epsilon = 1e-6);
+    }
+
+    #[test]
+    fn checker_pattern() {
+        let lut = TestPattern::Checker.create(3);
+        assert_eq!([0.0, 0.0, 0.0], rgb(&lut, 0, 0, 0));
+        assert_eq!([0.0, 1.0, 0.5], rgb(&lut, 2, 0, 1));
+        assert_eq!([0.5, 0.5, 1.0], rgb(&lut, 1, 1, 2));
+        assert_eq!([0.5, 1.0, 1.0], rgb(&lut, 1, 0, 0));
+    }
+
+    #[test]
+    fn hue_pattern() {
+        let lut = TestPattern::Hue.create(3);
+        assert_eq!([0.0, 0.0, 1.0], rgb(&lut, 2, 0, 0));
+        assert_eq!([0.5, 1.0, 0.0], rgb(&lut, 0, 1, 2));
+        assert_eq!([1.0, 1.0, 1.0], rgb(&lut, 2, 2, 2));
+    }
+}
@@ -0,0 +1,310 @@
+use std::convert::{TryFrom, TryInto};
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{format, Lut3dLinear, LutError};
+
+/// Reads the .cube and .nutexb LUTs in `dir` as the frames of an animated sequence.
+/// Files are sorted by name with numbered names like `frame2.cube` before `frame10.cube`.
+/// Files with other extensions are ignored.
+pub fn read_sequence_dir<P: AsRef<Path>>(dir: P) -> Result<Vec<Lut3dLinear>, LutError> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let supported = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(format::from_extension)
+            .is_some();
+        if path.is_file() && supported {
+            paths.push(path);
+        }
+    }
+
+    if paths.is_empty() {
+        return Err(LutError::Parse("No .cube or .nutexb files found.".into()));
+    }
+
+    paths.sort_by_cached_key(|p| frame_key(p));
+    read_sequence_files(&paths)
+}
+
+/// Reads each of the .cube or .nutexb `paths` in order as the frames of an animated sequence.
+pub fn read_sequence_files<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<Lut3dLinear>, LutError> {
+    paths
+        .iter()
+        .map(|path| {
+            let path = path.as_ref();
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            let format = format::from_extension(extension)
+                .ok_or_else(|| LutError::UnsupportedFormat(format!("{:?}", path)))?;
+            format.read(&mut BufReader::new(File::open(path)?))
+        })
+        .collect()
+}
+
+/// Samples an animated sequence at time `t` from `0.0` for the first frame to `1.0` for the last frame.
+/// Times between frames use [Lut3dLinear::blend] on the two nearest frames.
+/// Returns `None` if `luts` is empty.
+pub fn sample_sequence(luts: &[Lut3dLinear], t: f32) -> Option<Lut3dLinear> {
+    let last = luts.len().checked_sub(1)?;
+    let position = t.clamp(0.0, 1.0) * last as f32;
+    let i = (position.floor() as usize).min(last);
+    let next = (i + 1).min(last);
+    Some(luts[i].blend(&luts[next], position - i as f32))
+}
+
+/// The magic bytes at the start of a sequence file.
+const SEQUENCE_MAGIC: &[u8; 4] = b"LSEQ";
+
+/// Writes `luts` as the frames of a single sequence file.
+///
+/// The format is the magic bytes `LSEQ` followed by the LUT size and the frame count as little endian `u32`.
+/// The RGBA values of each frame follow as little endian `f32` in the same order as [Lut3dLinear::data].
+/// All frames must have the same non zero size.
+pub fn write_sequence<W: Write>(luts: &[Lut3dLinear], writer: &mut W) -> Result<(), LutError> {
+    let size = luts.first().map(|lut| lut.size).unwrap_or(0);
+    if luts.iter().any(|lut| lut.size != size) {
+        return Err(LutError::InvalidDimensions(
+            "Invalid dimensions. Expected all frames to have the same size.",
+        ));
+    }
+    if !luts.is_empty() && size == 0 {
+        return Err(LutError::InvalidDimensions(
+            "Invalid dimensions. Expected a size of at least 1.",
+        ));
+    }
+
+    let header_value = |value: usize| {
+        u32::try_from(value).map_err(|_| {
+            LutError::InvalidDimensions(
+                "Invalid dimensions. The size and frame count must fit in 32 bits.",
+            )
+        })
+    };
+    let size_bytes = header_value(size)?.to_le_bytes();
+    let frame_count_bytes = header_value(luts.len())?.to_le_bytes();
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(SEQUENCE_MAGIC);
+    bytes.extend_from_slice(&size_bytes);
+    bytes.extend_from_slice(&frame_count_bytes);
+    for lut in luts {
+        crate::validate_data_length(lut)?;
+        bytes.extend(lut.data.iter().flat_map(|f| f.to_le_bytes()));
+    }
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Reads the frames of a sequence file created with [write_sequence].
+pub fn read_sequence<R: Read>(reader: &mut R) -> Result<Vec<Lut3dLinear>, LutError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    if bytes.len() < 12 || &bytes[..4] != SEQUENCE_MAGIC {
+        return Err(LutError::Parse("Invalid sequence file header.".into()));
+    }
+    let read_u32 =
+        |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+    let size = read_u32(4);
+    let frame_count = read_u32(8);
+    if size == 0 && frame_count > 0 {
+        return Err(LutError::Parse(
+            "Expected a size of at least 1 for the frames.".into(),
+        ));
+    }
+
+    // Check the length before parsing to avoid overflow or large allocations from invalid headers.
+    let data = &bytes[12..];
+    let expected_len = (size as u64)
+        .checked_pow(3)
+        .and_then(|n| n.checked_mul(4 * 4))
+        .and_then(|n| n.checked_mul(frame_count as u64));
+    if expected_len != Some(data.len() as u64) {
+        return Err(LutError::Parse(format!(
+            "Expected {} frames of size {} but found {} bytes of data.",
+            frame_count,
+            size,
+            data.len()
+        )));
+    }
+
+    let values: Vec<f32> = data
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    let values_per_frame = size * size * size * 4;
+    Ok(values
+        .chunks_exact(values_per_frame.max(1))
+        .map(|data| Lut3dLinear {
+            size,
+            data: data.to_vec(),
+        })
+        .collect())
+}
+
+// Sort by the name without trailing digits and then by the value of the digits.
+fn frame_key(path: &Path) -> (String, u64, PathBuf) {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let prefix = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = stem[prefix.len()..].parse().unwrap_or(0);
+    (prefix.to_string(), number, path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant_lut(value: f32) -> Lut3dLinear {
+        Lut3dLinear::from_matrix(2, [[0.0; 3]; 3], [value; 3])
+    }
+
+    #[test]
+    fn sample_two_frames_half() {
+        let luts = [constant_lut(0.0), constant_lut(1.0)];
+        let lut = sample_sequence(&luts, 0.5).unwrap();
+        assert_eq!(constant_lut(0.5), lut);
+    }
+
+    #[test]
+    fn sample_frames() {
+        let luts = [constant_lut(0.0), constant_lut(0.5), constant_lut(1.0)];
+        assert_eq!(constant_lut(0.0), sample_sequence(&luts, 0.0).unwrap());
+        assert_eq!(constant_lut(0.25), sample_sequence(&luts, 0.25).unwrap());
+        assert_eq!(constant_lut(1.0), sample_sequence(&luts, 1.0).unwrap());
+        assert_eq!(constant_lut(1.0), sample_sequence(&luts, 2.0).unwrap());
+        assert_eq!(constant_lut(0.0), sample_sequence(&luts, -1.0).unwrap());
+    }
+
+    #[test]
+    fn sample_single_frame() {
+        let luts = [constant_lut(0.25)];
+        assert_eq!(constant_lut(0.25), sample_sequence(&luts, 0.5).unwrap());
+    }
+
+    #[test]
+    fn sample_empty() {
+        assert_eq!(None, sample_sequence(&[], 0.5));
+    }
+
+    #[test]
+    fn read_dir_numbered_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let cube = format::from_extension("cube").unwrap();
+        for (name, value) in [
+            ("frame10.cube", 1.0),
+            ("frame2.cube", 0.5),
+            ("frame1.cube", 0.0),
+        ] {
+            let mut file = File::create(dir.path().join(name)).unwrap();
+            cube.write(&constant_lut(value), &mut file).unwrap();
+        }
+        std::fs::write(dir.path().join("notes.txt"), "frames").unwrap();
+
+        let luts = read_sequence_dir(dir.path()).unwrap();
+        assert_eq!(
+            vec![constant_lut(0.0), constant_lut(0.5), constant_lut(1.0)],
+            luts
+        );
+    }
+
+    #[test]
+    fn read_dir_no_luts() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "frames").unwrap();
+        assert!(matches!(
+            read_sequence_dir(dir.path()),
+            Err(LutError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn read_files_unsupported_extension() {
+        assert!(matches!(
+            read_sequence_files(&["frame.txt"]),
+            Err(LutError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn write_read_two_frames() {
+        let luts = vec![Lut3dLinear::default_stage(), Lut3dLinear::identity()];
+        let mut bytes = Vec::new();
+        write_sequence(&luts, &mut bytes).unwrap();
+        assert_eq!(b"LSEQ", &bytes[..4]);
+        assert_eq!(12 + 2 * 16 * 16 * 16 * 4 * 4, bytes.len());
+
+        assert_eq!(luts, read_sequence(&mut bytes.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn write_different_sizes() {
+        let luts = [constant_lut(0.0), Lut3dLinear::identity()];
+        assert!(matches!(
+            write_sequence(&luts, &mut Vec::new()),
+            Err(LutError::InvalidDimensions(_))
+        ));
+    }
+
+    #[test]
+    fn write_zero_size() {
+        let luts = [Lut3dLinear::from_fn(0, |c| c)];
+        assert!(matches!(
+            write_sequence(&luts, &mut Vec::new()),
+            Err(LutError::InvalidDimensions(_))
+        ));
+    }
+
+    #[test]
+    fn write_read_no_frames() {
+        let mut bytes = Vec::new();
+        write_sequence(&[], &mut bytes).unwrap();
+        assert!(read_sequence(&mut bytes.as_slice()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn read_zero_size() {
+        let mut bytes = b"LSEQ".to_vec();
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            read_sequence(&mut bytes.as_slice()),
+            Err(LutError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn read_invalid_header() {
+        assert!(matches!(
+            read_sequence(&mut &b"LUT_3D_SIZE 2"[..]),
+            Err(LutError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn read_invalid_size() {
+        let mut bytes = b"LSEQ".to_vec();
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            read_sequence(&mut bytes.as_slice()),
+            Err(LutError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn read_truncated_frames() {
+        let mut bytes = Vec::new();
+        write_sequence(&[constant_lut(0.0), constant_lut(1.0)], &mut bytes).unwrap();
+        bytes.truncate(bytes.len() - 4);
+        assert!(matches!(
+            read_sequence(&mut bytes.as_slice()),
+            Err(LutError::Parse(_))
+        ));
+    }
+}